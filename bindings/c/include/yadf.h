@@ -0,0 +1,131 @@
+#ifndef YADF_H
+#define YADF_H
+
+/* Generated by cbindgen from bindings/c/src/lib.rs, do not edit: run `YADF_BLESS=1 cargo test -p yadf-c` instead. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The configuration of a scan, see `yadf --help` for what each option does.
+ */
+typedef struct YadfConfig YadfConfig;
+
+/**
+ * The groups of duplicate files found by a scan.
+ */
+typedef struct YadfGroups YadfGroups;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * The message of the last error on this thread, or null if there was none.
+ * It lives until the next failing call on this thread.
+ */
+const char *yadf_last_error(void);
+
+/**
+ * A configuration without roots, with the default algorithm and no
+ * filters.
+ */
+YadfConfig *yadf_config_new(void);
+
+void yadf_config_free(YadfConfig *config);
+
+/**
+ * Adds a path to scan, a directory to walk or a file.
+ *
+ * # Safety
+ *
+ * `path` must be null or point to a NUL-terminated string.
+ */
+int32_t yadf_config_add_root(YadfConfig *config, const char *path);
+
+/**
+ * Sets the hashing algorithm by its command line name, such as `"blake3"`.
+ *
+ * # Safety
+ *
+ * `name` must be null or point to a NUL-terminated string.
+ */
+int32_t yadf_config_set_algorithm(YadfConfig *config, const char *name);
+
+/**
+ * Only scans the files whose name matches this Perl-style regex.
+ *
+ * # Safety
+ *
+ * `regex` must be null or point to a NUL-terminated string.
+ */
+int32_t yadf_config_set_regex(YadfConfig *config, const char *regex);
+
+/**
+ * Only scans the files whose name matches this glob.
+ *
+ * # Safety
+ *
+ * `glob` must be null or point to a NUL-terminated string.
+ */
+int32_t yadf_config_set_glob(YadfConfig *config, const char *glob);
+
+int32_t yadf_config_set_min_size(YadfConfig *config, uint64_t bytes);
+
+int32_t yadf_config_set_max_size(YadfConfig *config, uint64_t bytes);
+
+int32_t yadf_config_set_max_depth(YadfConfig *config, size_t depth);
+
+/**
+ * Whether hard links to the same file count as duplicates. Ignored where
+ * hard links can't be told apart.
+ */
+int32_t yadf_config_set_hard_links(YadfConfig *config, bool enabled);
+
+/**
+ * Concurrency for the I/O-bound hashing phases, the number of CPUs by
+ * default.
+ */
+int32_t yadf_config_set_io_threads(YadfConfig *config, size_t threads);
+
+/**
+ * Scans as configured, and returns the groups of duplicates, each with its
+ * files in order, or null if `config` is null or the scan panicked. The
+ * configuration can be scanned again.
+ */
+YadfGroups *yadf_scan(const YadfConfig *config);
+
+void yadf_groups_free(YadfGroups *groups);
+
+/**
+ * The number of groups, 0 if `groups` is null.
+ */
+size_t yadf_groups_len(const YadfGroups *groups);
+
+/**
+ * The number of files in the group at `group`, 0 if there is none.
+ */
+size_t yadf_group_len(const YadfGroups *groups, size_t group);
+
+/**
+ * The hex hash of the group at `group`, or null if there is none.
+ *
+ * It is the hash of the whole content for files of at least 4 KiB, and also
+ * covers the size for smaller ones.
+ */
+const char *yadf_group_hash(const YadfGroups *groups, size_t group);
+
+/**
+ * The path of the file at `file` in the group at `group`, or null if there
+ * is none.
+ */
+const char *yadf_group_path(const YadfGroups *groups, size_t group, size_t file);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* YADF_H */
@@ -0,0 +1,447 @@
+//! C bindings for `yadf`, see `include/yadf.h`.
+//!
+//! A `YadfConfig` gathers the roots and filters of a scan, `yadf_scan` runs
+//! it, with the same walk, prefetching and hashing passes as the command
+//! line, and hands back the groups of duplicates as a `YadfGroups`, indexed
+//! by group then by file. Every handle is freed by its own `_free` function,
+//! and every string a `YadfGroups` gives out lives as long as it does.
+//!
+//! Functions which can fail return `0` on success and `-1` otherwise, the
+//! reason being left for `yadf_last_error`. A null handle is an error, never
+//! undefined behaviour.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::path::PathBuf;
+use yadf::{Algorithm, ContentHash, HashBytes};
+
+/// The configuration of a scan, see `yadf --help` for what each option does.
+#[derive(Debug, Default)]
+pub struct YadfConfig {
+    paths: Vec<PathBuf>,
+    algorithm: Algorithm,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    max_depth: Option<usize>,
+    regex: Option<yadf::regex::Regex>,
+    glob: Option<yadf::globset::Glob>,
+    hard_links: bool,
+    io_threads: Option<usize>,
+}
+
+/// The groups of duplicate files found by a scan.
+#[derive(Debug)]
+pub struct YadfGroups {
+    groups: Vec<Group>,
+}
+
+#[derive(Debug)]
+struct Group {
+    hash: CString,
+    files: Vec<CString>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Records `error` for `yadf_last_error`, and returns the failure code.
+fn fail(error: impl std::fmt::Display) -> i32 {
+    let message = CString::new(error.to_string().replace('\0', "\\0")).expect("NULs were escaped");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    -1
+}
+
+/// The string at `ptr`, or an error naming `what` if it is null or not
+/// UTF-8.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a NUL-terminated string.
+unsafe fn to_str<'a>(ptr: *const c_char, what: &str) -> Result<&'a str, String> {
+    if ptr.is_null() {
+        return Err(format!("{what} is null"));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|error| format!("{what} is not UTF-8: {error}"))
+}
+
+/// The path at `ptr`, its bytes taken as they are where the platform allows
+/// it.
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a NUL-terminated string.
+unsafe fn to_path(ptr: *const c_char) -> Result<PathBuf, String> {
+    if ptr.is_null() {
+        return Err("path is null".to_owned());
+    }
+    let bytes = CStr::from_ptr(ptr).to_bytes();
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Ok(std::ffi::OsStr::from_bytes(bytes).into())
+    }
+    #[cfg(not(unix))]
+    {
+        std::str::from_utf8(bytes)
+            .map(Into::into)
+            .map_err(|error| format!("path is not UTF-8: {error}"))
+    }
+}
+
+fn to_c_string(path: &std::path::Path) -> CString {
+    #[cfg(unix)]
+    let bytes = {
+        use std::os::unix::ffi::OsStrExt;
+        path.as_os_str().as_bytes().to_vec()
+    };
+    #[cfg(not(unix))]
+    let bytes = path.to_string_lossy().into_owned().into_bytes();
+    CString::new(bytes).expect("paths hold no NUL")
+}
+
+/// The message of the last error on this thread, or null if there was none.
+/// It lives until the next failing call on this thread.
+#[no_mangle]
+pub extern "C" fn yadf_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |m| m.as_ptr())
+    })
+}
+
+/// A configuration without roots, with the default algorithm and no
+/// filters.
+#[no_mangle]
+pub extern "C" fn yadf_config_new() -> Box<YadfConfig> {
+    Box::default()
+}
+
+#[no_mangle]
+pub extern "C" fn yadf_config_free(config: Option<Box<YadfConfig>>) {
+    drop(config);
+}
+
+/// Adds a path to scan, a directory to walk or a file.
+///
+/// # Safety
+///
+/// `path` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn yadf_config_add_root(
+    config: Option<&mut YadfConfig>,
+    path: *const c_char,
+) -> i32 {
+    let Some(config) = config else {
+        return fail("config is null");
+    };
+    match to_path(path) {
+        Ok(path) => {
+            config.paths.push(path);
+            0
+        }
+        Err(error) => fail(error),
+    }
+}
+
+/// Sets the hashing algorithm by its command line name, such as `"blake3"`.
+///
+/// # Safety
+///
+/// `name` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn yadf_config_set_algorithm(
+    config: Option<&mut YadfConfig>,
+    name: *const c_char,
+) -> i32 {
+    let Some(config) = config else {
+        return fail("config is null");
+    };
+    match to_str(name, "algorithm").and_then(str::parse) {
+        Ok(algorithm) => {
+            config.algorithm = algorithm;
+            0
+        }
+        Err(error) => fail(error),
+    }
+}
+
+/// Only scans the files whose name matches this Perl-style regex.
+///
+/// # Safety
+///
+/// `regex` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn yadf_config_set_regex(
+    config: Option<&mut YadfConfig>,
+    regex: *const c_char,
+) -> i32 {
+    let Some(config) = config else {
+        return fail("config is null");
+    };
+    let regex = to_str(regex, "regex")
+        .and_then(|regex| yadf::regex::Regex::new(regex).map_err(|error| error.to_string()));
+    match regex {
+        Ok(regex) => {
+            config.regex = Some(regex);
+            0
+        }
+        Err(error) => fail(error),
+    }
+}
+
+/// Only scans the files whose name matches this glob.
+///
+/// # Safety
+///
+/// `glob` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn yadf_config_set_glob(
+    config: Option<&mut YadfConfig>,
+    glob: *const c_char,
+) -> i32 {
+    let Some(config) = config else {
+        return fail("config is null");
+    };
+    let glob = to_str(glob, "glob")
+        .and_then(|glob| yadf::globset::Glob::new(glob).map_err(|error| error.to_string()));
+    match glob {
+        Ok(glob) => {
+            config.glob = Some(glob);
+            0
+        }
+        Err(error) => fail(error),
+    }
+}
+
+/// Sets a numeric option through `set`, failing on a null config.
+fn set(config: Option<&mut YadfConfig>, set: impl FnOnce(&mut YadfConfig)) -> i32 {
+    match config {
+        Some(config) => {
+            set(config);
+            0
+        }
+        None => fail("config is null"),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn yadf_config_set_min_size(config: Option<&mut YadfConfig>, bytes: u64) -> i32 {
+    set(config, |config| config.min_size = Some(bytes))
+}
+
+#[no_mangle]
+pub extern "C" fn yadf_config_set_max_size(config: Option<&mut YadfConfig>, bytes: u64) -> i32 {
+    set(config, |config| config.max_size = Some(bytes))
+}
+
+#[no_mangle]
+pub extern "C" fn yadf_config_set_max_depth(config: Option<&mut YadfConfig>, depth: usize) -> i32 {
+    set(config, |config| config.max_depth = Some(depth))
+}
+
+/// Whether hard links to the same file count as duplicates. Ignored where
+/// hard links can't be told apart.
+#[no_mangle]
+pub extern "C" fn yadf_config_set_hard_links(
+    config: Option<&mut YadfConfig>,
+    enabled: bool,
+) -> i32 {
+    set(config, |config| config.hard_links = enabled)
+}
+
+/// Concurrency for the I/O-bound hashing phases, the number of CPUs by
+/// default.
+#[no_mangle]
+pub extern "C" fn yadf_config_set_io_threads(
+    config: Option<&mut YadfConfig>,
+    threads: usize,
+) -> i32 {
+    set(config, |config| config.io_threads = Some(threads))
+}
+
+/// Scans as configured, and returns the groups of duplicates, each with its
+/// files in order, or null if `config` is null or the scan panicked. The
+/// configuration can be scanned again.
+#[no_mangle]
+pub extern "C" fn yadf_scan(config: Option<&YadfConfig>) -> Option<Box<YadfGroups>> {
+    let Some(config) = config else {
+        fail("config is null");
+        return None;
+    };
+    // A panic must not unwind into the caller's frames.
+    let scan = std::panic::catch_unwind(|| scan(config));
+    match scan {
+        Ok(groups) => Some(Box::new(groups)),
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown cause");
+            fail(format!("the scan panicked: {message}"));
+            None
+        }
+    }
+}
+
+fn scan(config: &YadfConfig) -> YadfGroups {
+    let counter = config.build().scan_with(config.algorithm);
+    let groups = counter
+        .into_inner()
+        .into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|(hash, files)| {
+            let mut files: Vec<_> = files.iter().map(|path| path.as_ref()).collect();
+            files.sort();
+            Group {
+                hash: hex(&hash),
+                files: files.into_iter().map(to_c_string).collect(),
+            }
+        })
+        .collect();
+    YadfGroups { groups }
+}
+
+fn hex(hash: &ContentHash) -> CString {
+    CString::new(hash.hex().to_string()).expect("hex digits hold no NUL")
+}
+
+#[no_mangle]
+pub extern "C" fn yadf_groups_free(groups: Option<Box<YadfGroups>>) {
+    drop(groups);
+}
+
+/// The number of groups, 0 if `groups` is null.
+#[no_mangle]
+pub extern "C" fn yadf_groups_len(groups: Option<&YadfGroups>) -> usize {
+    groups.map_or(0, |groups| groups.groups.len())
+}
+
+/// The number of files in the group at `group`, 0 if there is none.
+#[no_mangle]
+pub extern "C" fn yadf_group_len(groups: Option<&YadfGroups>, group: usize) -> usize {
+    get(groups, group).map_or(0, |group| group.files.len())
+}
+
+/// The hex hash of the group at `group`, or null if there is none.
+///
+/// It is the hash of the whole content for files of at least 4 KiB, and also
+/// covers the size for smaller ones.
+#[no_mangle]
+pub extern "C" fn yadf_group_hash(groups: Option<&YadfGroups>, group: usize) -> *const c_char {
+    get(groups, group).map_or(std::ptr::null(), |group| group.hash.as_ptr())
+}
+
+/// The path of the file at `file` in the group at `group`, or null if there
+/// is none.
+#[no_mangle]
+pub extern "C" fn yadf_group_path(
+    groups: Option<&YadfGroups>,
+    group: usize,
+    file: usize,
+) -> *const c_char {
+    get(groups, group)
+        .and_then(|group| group.files.get(file))
+        .map_or(std::ptr::null(), |path| path.as_ptr())
+}
+
+fn get(groups: Option<&YadfGroups>, group: usize) -> Option<&Group> {
+    groups.and_then(|groups| groups.groups.get(group))
+}
+
+impl YadfConfig {
+    #[cfg(unix)]
+    fn build(&self) -> yadf::Yadf<PathBuf> {
+        yadf::Yadf::builder()
+            .paths(self.paths.clone())
+            .minimum_file_size(self.min_size)
+            .maximum_file_size(self.max_size)
+            .max_depth(self.max_depth)
+            .regex(self.regex.clone())
+            .glob(self.glob.clone())
+            .hard_links(self.hard_links)
+            .io_threads(self.io_threads.unwrap_or_else(yadf::default_io_threads))
+            .build()
+    }
+
+    #[cfg(not(unix))]
+    fn build(&self) -> yadf::Yadf<PathBuf> {
+        yadf::Yadf::builder()
+            .paths(self.paths.clone())
+            .minimum_file_size(self.min_size)
+            .maximum_file_size(self.max_size)
+            .max_depth(self.max_depth)
+            .regex(self.regex.clone())
+            .glob(self.glob.clone())
+            .io_threads(self.io_threads.unwrap_or_else(yadf::default_io_threads))
+            .build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_through_the_c_api() {
+        let name = format!("yadf-c-scan_through_the_c_api-{}", std::process::id());
+        let root = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        for (name, content) in [("a", "same"), ("b", "same"), ("c", "other")] {
+            std::fs::write(root.join(name), content).unwrap();
+        }
+        let root_c = to_c_string(&root);
+        let mut config = yadf_config_new();
+        unsafe {
+            assert_eq!(yadf_config_add_root(Some(&mut config), root_c.as_ptr()), 0);
+            assert_eq!(
+                yadf_config_set_algorithm(Some(&mut config), c"sha512".as_ptr()),
+                -1
+            );
+            let error = CStr::from_ptr(yadf_last_error()).to_str().unwrap();
+            assert!(error.contains("sha512"), "{error}");
+            assert_eq!(
+                yadf_config_set_algorithm(Some(&mut config), c"blake3".as_ptr()),
+                0
+            );
+        }
+        let groups = yadf_scan(Some(&config));
+        let groups = groups.as_deref();
+        assert_eq!(yadf_groups_len(groups), 1);
+        assert_eq!(yadf_group_len(groups, 0), 2);
+        assert!(yadf_group_path(groups, 0, 2).is_null());
+        assert!(yadf_group_hash(groups, 1).is_null());
+        for (index, name) in ["a", "b"].into_iter().enumerate() {
+            let path = unsafe { CStr::from_ptr(yadf_group_path(groups, 0, index)) };
+            assert_eq!(path.to_bytes(), to_c_string(&root.join(name)).as_bytes());
+        }
+        assert_eq!(yadf_groups_len(None), 0);
+        assert!(yadf_scan(None).is_none());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    /// Run with `YADF_BLESS=1` to write the header again.
+    #[test]
+    fn header_is_up_to_date() {
+        let dir = env!("CARGO_MANIFEST_DIR");
+        let config = cbindgen::Config::from_file(format!("{dir}/cbindgen.toml")).unwrap();
+        let mut generated = Vec::new();
+        cbindgen::generate_with_config(dir, config)
+            .unwrap()
+            .write(&mut generated);
+        let path = format!("{dir}/include/yadf.h");
+        if std::env::var_os("YADF_BLESS").is_some() {
+            std::fs::write(&path, &generated).unwrap();
+        }
+        let header = std::fs::read(&path).unwrap_or_default();
+        assert!(
+            header == generated,
+            "{path} is out of date, run `YADF_BLESS=1 cargo test -p yadf-c`"
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//! Python bindings for `yadf`.
+//!
+//! A `yadf.Yadf` holds a scan's configuration, with the options of the
+//! library's builder as keyword arguments; its scans hand back plain lists
+//! and dicts of `pathlib.Path`s, rather than JSON to parse, sorted so that
+//! two scans of the same files give the same lists.
+//!
+//! ```python
+//! import yadf
+//!
+//! config = yadf.Yadf(["/srv/photos"], algorithm="blake3", min_size=1 << 20)
+//! for group in config.groups():
+//!     print(group["hash"], group["size"], group["files"])
+//! ```
+
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::path::PathBuf;
+use yadf::{Algorithm, HashBytes};
+
+/// The configuration of a scan, see `yadf --help` for what each option does.
+///
+/// Invalid options raise a `ValueError` here rather than when scanning. A
+/// path which doesn't exist raises a `FileNotFoundError` when scanning; any
+/// other file which can't be read is only logged, and left out.
+#[pyclass(name = "Yadf", module = "yadf", frozen)]
+struct Config {
+    paths: Vec<PathBuf>,
+    algorithm: Algorithm,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    max_depth: Option<usize>,
+    regex: Option<yadf::regex::Regex>,
+    glob: Option<yadf::globset::Glob>,
+    hard_links: bool,
+    exact_files: bool,
+    io_threads: Option<usize>,
+}
+
+#[pymethods]
+impl Config {
+    #[new]
+    #[pyo3(signature = (
+        paths,
+        *,
+        algorithm = "highway",
+        min_size = None,
+        max_size = None,
+        max_depth = None,
+        regex = None,
+        glob = None,
+        hard_links = false,
+        exact_files = false,
+        io_threads = None,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        paths: Vec<PathBuf>,
+        algorithm: &str,
+        min_size: Option<u64>,
+        max_size: Option<u64>,
+        max_depth: Option<usize>,
+        regex: Option<&str>,
+        glob: Option<&str>,
+        hard_links: bool,
+        exact_files: bool,
+        io_threads: Option<usize>,
+    ) -> PyResult<Self> {
+        let invalid = |error: &dyn std::fmt::Display| PyValueError::new_err(error.to_string());
+        Ok(Self {
+            paths,
+            algorithm: algorithm.parse().map_err(|error| invalid(&error))?,
+            min_size,
+            max_size,
+            max_depth,
+            regex: regex
+                .map(yadf::regex::Regex::new)
+                .transpose()
+                .map_err(|error| invalid(&error))?,
+            glob: glob
+                .map(yadf::globset::Glob::new)
+                .transpose()
+                .map_err(|error| invalid(&error))?,
+            hard_links,
+            exact_files,
+            io_threads,
+        })
+    }
+
+    /// Every group of duplicate files, as a list of paths.
+    fn duplicates(&self, py: Python<'_>) -> PyResult<Vec<Vec<PathBuf>>> {
+        self.check_paths()?;
+        let counter = py.detach(|| self.build(false).scan_with(self.algorithm));
+        let mut groups: Vec<Vec<PathBuf>> = counter
+            .duplicates()
+            .iter()
+            .map(|group| {
+                let mut files: Vec<_> = group
+                    .iter()
+                    .map(|path| path.as_ref().to_path_buf())
+                    .collect();
+                files.sort();
+                files
+            })
+            .collect();
+        groups.sort();
+        Ok(groups)
+    }
+
+    /// Every group of duplicate files, as a dict of their `hash` (hex),
+    /// their `size` in bytes, and their `files`.
+    ///
+    /// The hash is that of the whole content for files of at least 4 KiB,
+    /// and also covers the size for smaller ones. It is `None` for an
+    /// algorithm whose hashes change from one run to the next, like
+    /// `ahash`, since it couldn't be compared with anything.
+    fn groups<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyDict>>> {
+        self.check_paths()?;
+        let counter = py.detach(|| self.build(true).scan_with(self.algorithm));
+        let stable = self.algorithm.is_stable();
+        let mut sorted = Vec::new();
+        for (hash, files) in counter.into_inner() {
+            if files.len() < 2 {
+                continue;
+            }
+            let size = files[0].info().map(|info| info.size);
+            let mut files: Vec<_> = files
+                .iter()
+                .map(|path| path.as_ref().to_path_buf())
+                .collect();
+            files.sort();
+            let hash = stable.then(|| hash.hex().to_string());
+            sorted.push((files, hash, size));
+        }
+        sorted.sort();
+        let mut groups = Vec::new();
+        for (files, hash, size) in sorted {
+            let group = PyDict::new(py);
+            group.set_item("hash", hash)?;
+            group.set_item("size", size)?;
+            group.set_item("files", files)?;
+            groups.push(group);
+        }
+        Ok(groups)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Yadf({:?}, algorithm={:?})",
+            self.paths,
+            self.algorithm.name()
+        )
+    }
+}
+
+impl Config {
+    /// Raises an `OSError`, a `FileNotFoundError` most likely, for the first
+    /// path which can't be looked at: the scan would only log it, and find
+    /// nothing there.
+    fn check_paths(&self) -> PyResult<()> {
+        for path in &self.paths {
+            std::fs::symlink_metadata(path).map_err(|error| {
+                let errno = error.raw_os_error().unwrap_or_default();
+                PyOSError::new_err((errno, error.to_string(), path.clone()))
+            })?;
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn build(&self, file_info: bool) -> yadf::Yadf<PathBuf> {
+        yadf::Yadf::builder()
+            .paths(self.paths.clone())
+            .minimum_file_size(self.min_size)
+            .maximum_file_size(self.max_size)
+            .max_depth(self.max_depth)
+            .exact_files(self.exact_files)
+            .regex(self.regex.clone())
+            .glob(self.glob.clone())
+            .hard_links(self.hard_links)
+            .file_info(file_info)
+            .io_threads(self.io_threads.unwrap_or_else(yadf::default_io_threads))
+            .build()
+    }
+
+    #[cfg(not(unix))]
+    fn build(&self, file_info: bool) -> yadf::Yadf<PathBuf> {
+        yadf::Yadf::builder()
+            .paths(self.paths.clone())
+            .minimum_file_size(self.min_size)
+            .maximum_file_size(self.max_size)
+            .max_depth(self.max_depth)
+            .exact_files(self.exact_files)
+            .regex(self.regex.clone())
+            .glob(self.glob.clone())
+            .file_info(file_info)
+            .io_threads(self.io_threads.unwrap_or_else(yadf::default_io_threads))
+            .build()
+    }
+}
+
+/// The names of the hashing algorithms, for `Yadf(algorithm=...)`.
+#[pyfunction]
+fn algorithms() -> Vec<&'static str> {
+    Algorithm::ALL.iter().map(Algorithm::name).collect()
+}
+
+#[pymodule]
+#[pyo3(name = "yadf")]
+fn yadf_python(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Config>()?;
+    module.add_function(wrap_pyfunction!(algorithms, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,54 @@
+import pathlib
+import tempfile
+import unittest
+
+import yadf
+
+
+class TestYadf(unittest.TestCase):
+    def setUp(self):
+        self.dir = tempfile.TemporaryDirectory()
+        self.root = pathlib.Path(self.dir.name)
+        for name, content in [("a", b"same"), ("b", b"same"), ("c", b"other")]:
+            (self.root / name).write_bytes(content)
+
+    def tearDown(self):
+        self.dir.cleanup()
+
+    def test_duplicates(self):
+        groups = yadf.Yadf([self.root]).duplicates()
+        self.assertEqual(groups, [[self.root / "a", self.root / "b"]])
+
+    def test_groups(self):
+        [group] = yadf.Yadf([self.root], algorithm="sha256").groups()
+        self.assertEqual(group["size"], 4)
+        self.assertEqual(len(group["hash"]), 64)
+        self.assertEqual(group["files"], [self.root / "a", self.root / "b"])
+
+    def test_groups_of_unstable_algorithms_have_no_hash(self):
+        [group] = yadf.Yadf([self.root], algorithm="ahash").groups()
+        self.assertIsNone(group["hash"])
+        self.assertEqual(group["files"], [self.root / "a", self.root / "b"])
+
+    def test_exact_files(self):
+        config = yadf.Yadf([self.root / "a", self.root / "c"], exact_files=True)
+        self.assertEqual(config.duplicates(), [])
+
+    def test_missing_root(self):
+        config = yadf.Yadf([self.root / "missing"])
+        with self.assertRaises(FileNotFoundError) as raised:
+            config.duplicates()
+        self.assertEqual(raised.exception.filename, self.root / "missing")
+        with self.assertRaises(FileNotFoundError):
+            config.groups()
+
+    def test_invalid_options(self):
+        with self.assertRaises(ValueError):
+            yadf.Yadf([self.root], algorithm="sha512")
+        with self.assertRaises(ValueError):
+            yadf.Yadf([self.root], regex="(")
+        self.assertIn("blake3", yadf.algorithms())
+
+
+if __name__ == "__main__":
+    unittest.main()
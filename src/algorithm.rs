@@ -0,0 +1,222 @@
+//! The hashers `yadf` ships with, chosen at runtime rather than by a type
+//! parameter.
+
+use crate::hasher::{HashBytes, Hasher};
+use std::fmt;
+use std::str::FromStr;
+
+/// A hashing algorithm, for callers reading it from a configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "build-bin", derive(clap::ValueEnum))]
+#[cfg_attr(feature = "build-bin", clap(rename_all = "lower"))]
+pub enum Algorithm {
+    AHash,
+    Blake3,
+    Crc32c,
+    #[default]
+    Highway,
+    Md5,
+    MetroHash,
+    SeaHash,
+    Sha1,
+    Sha256,
+    XxHash,
+}
+
+/// Work which needs to know the hasher, chosen at runtime by
+/// [`Algorithm::dispatch`].
+pub trait WithHasher {
+    type Output;
+
+    fn run<H>(self) -> Self::Output
+    where
+        H: Hasher,
+        H::Hash: fmt::Debug + HashBytes;
+}
+
+/// A hash of any of the [`Algorithm`]s, its digest as the algorithm's
+/// checksum tool would print it.
+///
+/// Hashes of different algorithms can be told apart by their length at
+/// best, and shouldn't be compared.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ContentHash {
+    len: u8,
+    bytes: [u8; ContentHash::MAX_LEN],
+}
+
+impl Algorithm {
+    /// Every algorithm, in the order they are listed in.
+    pub const ALL: [Algorithm; 10] = [
+        Algorithm::AHash,
+        Algorithm::Blake3,
+        Algorithm::Crc32c,
+        Algorithm::Highway,
+        Algorithm::Md5,
+        Algorithm::MetroHash,
+        Algorithm::SeaHash,
+        Algorithm::Sha1,
+        Algorithm::Sha256,
+        Algorithm::XxHash,
+    ];
+
+    /// Runs `work` with this algorithm's hasher.
+    pub fn dispatch<W: WithHasher>(&self, work: W) -> W::Output {
+        match self {
+            Algorithm::AHash => work.run::<ahash::AHasher>(),
+            Algorithm::Blake3 => work.run::<blake3::Hasher>(),
+            Algorithm::Crc32c => work.run::<crc32c::Crc32cHasher>(),
+            Algorithm::Highway => work.run::<highway::HighwayHasher>(),
+            Algorithm::Md5 => work.run::<md5::Md5>(),
+            Algorithm::MetroHash => work.run::<metrohash::MetroHash128>(),
+            Algorithm::SeaHash => work.run::<seahash::SeaHasher>(),
+            Algorithm::Sha1 => work.run::<sha1::Sha1>(),
+            Algorithm::Sha256 => work.run::<sha2::Sha256>(),
+            Algorithm::XxHash => work.run::<twox_hash::XxHash3_128>(),
+        }
+    }
+
+    /// Whether the same content always gets the same hash, from one run to
+    /// the next and from one machine to another.
+    pub fn is_stable(&self) -> bool {
+        !matches!(self, Algorithm::AHash)
+    }
+
+    /// The name it goes by on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::AHash => "ahash",
+            Algorithm::Blake3 => "blake3",
+            Algorithm::Crc32c => "crc32c",
+            Algorithm::Highway => "highway",
+            Algorithm::Md5 => "md5",
+            Algorithm::MetroHash => "metrohash",
+            Algorithm::SeaHash => "seahash",
+            Algorithm::Sha1 => "sha1",
+            Algorithm::Sha256 => "sha256",
+            Algorithm::XxHash => "xxhash",
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Parses the [name](Algorithm::name), ignoring case.
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown algorithm: {s:?}"))
+    }
+}
+
+impl ContentHash {
+    /// The longest digest of the [`Algorithm`]s, in bytes.
+    pub const MAX_LEN: usize = 32;
+
+    /// Erases the type of `hash`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if its digest is longer than [`ContentHash::MAX_LEN`].
+    pub fn new<K: HashBytes>(hash: &K) -> Self {
+        let digest = hash.to_bytes();
+        let mut bytes = [0; Self::MAX_LEN];
+        bytes[..digest.len()].copy_from_slice(&digest);
+        Self {
+            len: digest.len() as u8,
+            bytes,
+        }
+    }
+
+    /// The digest, most significant byte first.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..usize::from(self.len)]
+    }
+}
+
+impl HashBytes for ContentHash {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl fmt::Debug for ContentHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ContentHash")
+            .field(&format_args!("{}", self.hex()))
+            .finish()
+    }
+}
+
+/// Scans with the algorithm given, its hashes type-erased.
+struct ScanWith<P: AsRef<std::path::Path>>(crate::Yadf<P>);
+
+impl<P: AsRef<std::path::Path>> WithHasher for ScanWith<P> {
+    type Output = crate::FileCounter<ContentHash>;
+
+    fn run<H>(self) -> Self::Output
+    where
+        H: Hasher,
+        H::Hash: fmt::Debug + HashBytes,
+    {
+        let bag = self.0.scan::<H>().into_inner();
+        bag.into_iter()
+            .map(|(hash, bucket)| (ContentHash::new(&hash), bucket))
+            .collect::<std::collections::BTreeMap<_, _>>()
+            .into()
+    }
+}
+
+impl<P: AsRef<std::path::Path>> crate::Yadf<P> {
+    /// Scans like [`Yadf::scan`](crate::Yadf::scan), with a hasher picked at
+    /// runtime.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use yadf::HashBytes;
+    /// # fn foo(paths: &[std::path::PathBuf], configured: &str) {
+    /// let algorithm: yadf::Algorithm = configured.parse().unwrap();
+    /// let counter = yadf::Yadf::builder()
+    ///     .paths(paths)
+    ///     .build()
+    ///     .scan_with(algorithm);
+    /// for group in counter.duplicates().with_hashes().iter() {
+    ///     println!("{}: {} files", group.hash.hex(), group.files.len());
+    /// }
+    /// # }
+    /// ```
+    pub fn scan_with(self, algorithm: Algorithm) -> crate::FileCounter<ContentHash> {
+        algorithm.dispatch(ScanWith(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_round_trip() {
+        for algorithm in Algorithm::ALL {
+            assert_eq!(algorithm.name().parse(), Ok(algorithm));
+        }
+        assert_eq!("SHA256".parse(), Ok(Algorithm::Sha256));
+        assert!("sha512".parse::<Algorithm>().is_err());
+    }
+
+    #[test]
+    fn content_hash_keeps_the_digest() {
+        let hash = ContentHash::new(&0xffu64);
+        assert_eq!(hash.as_bytes(), [0, 0, 0, 0, 0, 0, 0, 0xff]);
+        assert_eq!(hash.hex().to_string(), 0xffu64.hex().to_string());
+        assert!(ContentHash::new(&1u64) < hash);
+    }
+}
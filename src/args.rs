@@ -1,9 +1,10 @@
 use super::{Args, ReplicationFactor};
+use anyhow::Context;
 use clap::{CommandFactory, FromArgMatches};
 use std::env;
 use std::fmt;
-use std::io::BufRead;
-use std::path::PathBuf;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
 
 impl Args {
     pub fn max(&self) -> Option<u64> {
@@ -21,7 +22,7 @@ impl Args {
             .or(if self.no_empty { Some(1) } else { None })
     }
 
-    pub fn init_from_env() -> Self {
+    pub fn init_from_env() -> anyhow::Result<Self> {
         let long_version = env!("YADF_BUILD_VERSION").replace('|', "\n");
         let short_version = long_version.lines().next().unwrap().to_string();
         let app = Self::command()
@@ -30,14 +31,25 @@ impl Args {
             .after_help("For sizes, K/M/G/T[B|iB] suffixes can be used (case-insensitive).");
         let mut args = Self::from_arg_matches(&app.get_matches()).unwrap();
         init_logger(&args.verbosity);
-        args.build_paths();
-        args
+        args.build_paths()?;
+        Ok(args)
     }
 
-    fn build_paths(&mut self) {
-        if self.paths.is_empty() {
+    /// The list of files to scan instead of walking, with its separator.
+    pub fn file_list(&self) -> Option<(&Path, u8)> {
+        let lines = self.files_from.as_deref().map(|list| (list, b'\n'));
+        lines.or_else(|| self.files0_from.as_deref().map(|list| (list, b'\0')))
+    }
+
+    fn build_paths(&mut self) -> anyhow::Result<()> {
+        if let Some((list, separator)) = self.file_list() {
+            let paths = read_list(list, separator)?;
+            log::debug!("read {} paths from {:?}", paths.len(), list);
+            self.paths = paths;
+        } else if self.paths.is_empty() && self.command.is_none() {
             self.paths = default_paths()
         }
+        Ok(())
     }
 }
 
@@ -57,12 +69,10 @@ fn default_paths() -> Vec<PathBuf> {
     let mut paths = if std::io::IsTerminal::is_terminal(&stdin) {
         Vec::new()
     } else {
-        stdin
-            .lock()
-            .lines()
-            .map_while(Result::ok)
-            .map(Into::into)
-            .collect()
+        read_paths(stdin.lock(), b'\n').unwrap_or_else(|error| {
+            log::error!("{}, couldn't read paths from stdin", error);
+            Vec::new()
+        })
     };
     if paths.is_empty() {
         paths.push(env::current_dir().expect("couldn't get current working directory"));
@@ -70,6 +80,46 @@ fn default_paths() -> Vec<PathBuf> {
     paths
 }
 
+/// Reads the paths of `list`, or of stdin if it is `-`.
+fn read_list(list: &Path, separator: u8) -> anyhow::Result<Vec<PathBuf>> {
+    let context = || format!("reading the list of files: {:?}", list.display());
+    if list == Path::new("-") {
+        return read_paths(io::stdin().lock(), separator).with_context(context);
+    }
+    let file = std::fs::File::open(list).with_context(context)?;
+    read_paths(io::BufReader::new(file), separator).with_context(context)
+}
+
+/// Reads paths split by `separator`, skipping empty ones. Paths needn't be
+/// valid UTF-8 where the platform allows it, and lines may end with `\r\n`.
+fn read_paths(reader: impl BufRead, separator: u8) -> io::Result<Vec<PathBuf>> {
+    reader
+        .split(separator)
+        .map(|item| {
+            let mut bytes = item?;
+            if separator == b'\n' && bytes.last() == Some(&b'\r') {
+                bytes.pop();
+            }
+            Ok(bytes)
+        })
+        .filter(|item| !matches!(item, Ok(bytes) if bytes.is_empty()))
+        .map(|item| item.and_then(path_from_bytes))
+        .collect()
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> io::Result<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+    Ok(std::ffi::OsString::from_vec(bytes).into())
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> io::Result<PathBuf> {
+    String::from_utf8(bytes)
+        .map(Into::into)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
 impl Default for ReplicationFactor {
     fn default() -> Self {
         ReplicationFactor::Over(1)
@@ -137,4 +187,14 @@ mod tests {
             assert_eq!(&rf, expected);
         }
     }
+
+    #[test]
+    fn path_lists() {
+        let read = |list: &[u8], separator| read_paths(list, separator).unwrap();
+        let paths: Vec<PathBuf> = vec!["a b".into(), "c".into()];
+        assert_eq!(read(b"a b\nc\n", b'\n'), paths);
+        assert_eq!(read(b"a b\r\n\nc", b'\n'), paths);
+        assert_eq!(read(b"a b\0c\0", b'\0'), paths);
+        assert_eq!(read(b"a\nb\0", b'\0'), [PathBuf::from("a\nb")]);
+    }
 }
@@ -1,6 +1,7 @@
 mod display;
 mod replicates;
 mod serialize;
+mod set;
 
 use std::borrow::Borrow;
 use std::collections::btree_map::Entry;
@@ -1,5 +1,5 @@
 use super::{Display, Factor, Replicates};
-use std::collections::btree_map::Values;
+use std::collections::btree_map::{self, Values};
 
 /// [`Iterator`] adapter.
 #[derive(Debug)]
@@ -8,6 +8,13 @@ pub struct Iter<'a, K, V> {
     factor: Factor,
 }
 
+/// [`Iterator`] adapter, yielding each bucket along with its key.
+#[derive(Debug)]
+pub struct KeyedIter<'a, K, V> {
+    entries: btree_map::Iter<'a, K, Vec<V>>,
+    factor: Factor,
+}
+
 impl<K, V> Replicates<'_, K, V> {
     /// Iterator over the buckets.
     pub fn iter(&self) -> Iter<'_, K, V> {
@@ -17,6 +24,14 @@ impl<K, V> Replicates<'_, K, V> {
         }
     }
 
+    /// Iterator over the buckets and the keys they were grouped by.
+    pub fn iter_keyed(&self) -> KeyedIter<'_, K, V> {
+        KeyedIter {
+            entries: self.tree.0.iter(),
+            factor: self.factor.clone(),
+        }
+    }
+
     /// Returns an object that implements [`Display`](std::fmt::Display).
     ///
     /// Depending on the contents of the [`TreeBag`](super::TreeBag), the display object
@@ -52,6 +67,20 @@ impl<'a, K, V> Iterator for Iter<'a, K, V> {
     }
 }
 
+#[allow(clippy::manual_find)]
+impl<'a, K, V> Iterator for KeyedIter<'a, K, V> {
+    type Item = (&'a K, &'a Vec<V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (key, bucket) in &mut self.entries {
+            if self.factor.pass(bucket.len()) {
+                return Some((key, bucket));
+            }
+        }
+        None
+    }
+}
+
 impl Factor {
     fn pass(&self, x: usize) -> bool {
         match *self {
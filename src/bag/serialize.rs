@@ -1,5 +1,131 @@
 use super::{Replicates, TreeBag};
-use serde::ser::{Serialize, Serializer};
+use crate::hasher::HashBytes;
+use crate::path::{Detailed, Path};
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::collections::BTreeMap;
+
+/// A view serializing each bucket along with its hash, as
+/// `{"hash": "<hex>", "files": [...]}`.
+#[derive(Debug)]
+pub struct WithHashes<'a, K, V>(&'a Replicates<'a, K, V>);
+
+/// One bucket of [`WithHashes`].
+#[derive(Debug)]
+pub struct Group<'a, K, V> {
+    pub hash: &'a K,
+    pub files: &'a Vec<V>,
+}
+
+impl<'a, K, V> Replicates<'a, K, V> {
+    /// Provides a view which serializes the key of every bucket, hex-encoded,
+    /// along with its contents.
+    pub fn with_hashes(&'a self) -> WithHashes<'a, K, V> {
+        WithHashes(self)
+    }
+}
+
+/// A view serializing each bucket with its hash, the size of its files and
+/// the bytes taken by every copy but one, and each file with its
+/// [`FileInfo`](crate::FileInfo).
+#[derive(Debug)]
+pub struct Rich<'a, K>(WithHashes<'a, K, Path>);
+
+/// One bucket of [`Rich`].
+struct RichGroup<'a, K>(Group<'a, K, Path>);
+
+/// The files of a [`RichGroup`].
+struct DetailedFiles<'a>(&'a [Path]);
+
+impl<'a, K> Replicates<'a, K, Path> {
+    /// Provides a view which serializes every bucket with its hash and the
+    /// metadata of its files, for the scans that kept it.
+    pub fn rich(&'a self) -> Rich<'a, K> {
+        Rich(self.with_hashes())
+    }
+}
+
+impl<K> Group<'_, K, Path> {
+    /// Size of each file of the group, if the scan kept their metadata.
+    pub fn size(&self) -> Option<u64> {
+        Some(self.files.first()?.info()?.size)
+    }
+
+    /// Bytes taken by every copy but one.
+    pub fn wasted(&self) -> Option<u64> {
+        Some(self.size()? * (self.files.len() as u64).saturating_sub(1))
+    }
+}
+
+impl<'a, K, V> WithHashes<'a, K, V> {
+    /// Iterator over the buckets, each serializable on its own.
+    pub fn iter(&self) -> impl Iterator<Item = Group<'a, K, V>> + 'a {
+        self.0
+            .iter_keyed()
+            .map(|(hash, files)| Group { hash, files })
+    }
+}
+
+impl<K, V> Serialize for WithHashes<'_, K, V>
+where
+    K: HashBytes,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<K, V> Serialize for Group<'_, K, V>
+where
+    K: HashBytes,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut group = serializer.serialize_struct("Group", 2)?;
+        group.serialize_field("hash", &self.hash.hex())?;
+        group.serialize_field("files", self.files)?;
+        group.end()
+    }
+}
+
+impl<K: HashBytes> Serialize for Rich<'_, K> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.0.iter().map(RichGroup))
+    }
+}
+
+impl<K: HashBytes> Serialize for RichGroup<'_, K> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut group = serializer.serialize_struct("Group", 4)?;
+        group.serialize_field("hash", &self.0.hash.hex())?;
+        group.serialize_field("size", &self.0.size())?;
+        group.serialize_field("wasted", &self.0.wasted())?;
+        group.serialize_field("files", &DetailedFiles(self.0.files))?;
+        group.end()
+    }
+}
+
+impl Serialize for DetailedFiles<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_seq(self.0.iter().map(Detailed))
+    }
+}
 
 impl<K, V> Serialize for Replicates<'_, K, V>
 where
@@ -26,6 +152,21 @@ where
     }
 }
 
+/// Reads back what [`TreeBag`]'s `Serialize` writes: a map of keys to
+/// buckets.
+impl<'de, K, V> Deserialize<'de> for TreeBag<K, V>
+where
+    K: Deserialize<'de> + Ord,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        BTreeMap::deserialize(deserializer).map(Self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::super::TreeBag;
@@ -45,4 +186,28 @@ mod tests {
         let expected = r#"[["foo","bar"],["hello","world"]]"#;
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn round_trip() {
+        let counter: TreeBag<String, String> = vec![
+            ("a".to_string(), "hello".to_string()),
+            ("a".to_string(), "world".to_string()),
+            ("b".to_string(), "foo".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let json = serde_json::to_string(&counter).unwrap();
+        let result: TreeBag<String, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(result.as_inner(), counter.as_inner());
+    }
+
+    #[test]
+    fn json_with_hashes() {
+        let counter: TreeBag<u64, &str> = vec![(0xff, "foo"), (0xff, "bar"), (1, "ignored")]
+            .into_iter()
+            .collect();
+        let result = serde_json::to_string(&counter.duplicates().with_hashes()).unwrap();
+        let expected = r#"[{"hash":"00000000000000ff","files":["foo","bar"]}]"#;
+        assert_eq!(result, expected);
+    }
 }
@@ -0,0 +1,153 @@
+use super::TreeBag;
+use std::collections::btree_map::Entry;
+
+/// Set operations on the keys of two bags, for comparing scans made on
+/// different machines or at different times with the same hasher.
+///
+/// The plain versions keep the buckets as they are, concatenated where both
+/// bags have the key. The `_labelled` versions pair every value with the
+/// label of the bag it came from.
+impl<K: Ord, V> TreeBag<K, V> {
+    /// Every bucket of both bags, the values of `self` first where both
+    /// have the key.
+    pub fn merge(mut self, other: Self) -> Self {
+        for (key, bucket) in other.0 {
+            match self.0.entry(key) {
+                Entry::Vacant(entry) => {
+                    entry.insert(bucket);
+                }
+                Entry::Occupied(mut entry) => entry.get_mut().extend(bucket),
+            }
+        }
+        self
+    }
+
+    /// The buckets whose key is in both bags, the values of `self` first.
+    pub fn intersection(mut self, mut other: Self) -> Self {
+        self.0.retain(|key, bucket| match other.0.remove(key) {
+            Some(theirs) => {
+                bucket.extend(theirs);
+                true
+            }
+            None => false,
+        });
+        self
+    }
+
+    /// The buckets of `self` whose key isn't in `other`.
+    pub fn difference(mut self, other: &Self) -> Self {
+        self.0.retain(|key, _| !other.0.contains_key(key));
+        self
+    }
+
+    /// The buckets whose key is in one bag but not the other.
+    pub fn symmetric_difference(mut self, mut other: Self) -> Self {
+        self.0.retain(|key, _| other.0.remove(key).is_none());
+        self.0.append(&mut other.0);
+        self
+    }
+
+    /// [`merge`](Self::merge), each value labelled with the bag it came from.
+    pub fn merge_labelled<L: Clone>(
+        self,
+        label: L,
+        other: Self,
+        other_label: L,
+    ) -> TreeBag<K, (L, V)> {
+        self.labelled(label).merge(other.labelled(other_label))
+    }
+
+    /// [`intersection`](Self::intersection), each value labelled with the bag
+    /// it came from.
+    pub fn intersection_labelled<L: Clone>(
+        self,
+        label: L,
+        other: Self,
+        other_label: L,
+    ) -> TreeBag<K, (L, V)> {
+        self.labelled(label)
+            .intersection(other.labelled(other_label))
+    }
+
+    /// [`difference`](Self::difference), each value labelled with `label`:
+    /// none of `other`'s values are left.
+    pub fn difference_labelled<L: Clone>(self, label: L, other: &Self) -> TreeBag<K, (L, V)> {
+        self.difference(other).labelled(label)
+    }
+
+    /// [`symmetric_difference`](Self::symmetric_difference), each value
+    /// labelled with the bag it came from.
+    pub fn symmetric_difference_labelled<L: Clone>(
+        self,
+        label: L,
+        other: Self,
+        other_label: L,
+    ) -> TreeBag<K, (L, V)> {
+        self.labelled(label)
+            .symmetric_difference(other.labelled(other_label))
+    }
+
+    fn labelled<L: Clone>(self, label: L) -> TreeBag<K, (L, V)> {
+        let tree = self.0.into_iter().map(|(key, bucket)| {
+            let bucket = bucket.into_iter().map(|value| (label.clone(), value));
+            (key, bucket.collect())
+        });
+        TreeBag(tree.collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::TreeBag;
+
+    fn bags() -> (TreeBag<u8, &'static str>, TreeBag<u8, &'static str>) {
+        let ours = vec![(1, "a"), (2, "b"), (2, "c")].into_iter().collect();
+        let theirs = vec![(2, "d"), (3, "e")].into_iter().collect();
+        (ours, theirs)
+    }
+
+    #[test]
+    fn plain() {
+        let (ours, theirs) = bags();
+        let merged = ours.merge(theirs);
+        assert_eq!(merged[&1], ["a"]);
+        assert_eq!(merged[&2], ["b", "c", "d"]);
+        assert_eq!(merged[&3], ["e"]);
+
+        let (ours, theirs) = bags();
+        let common = ours.intersection(theirs);
+        assert_eq!(common.as_inner().keys().collect::<Vec<_>>(), [&2]);
+        assert_eq!(common[&2], ["b", "c", "d"]);
+
+        let (ours, theirs) = bags();
+        let only_ours = ours.difference(&theirs);
+        assert_eq!(only_ours.as_inner().keys().collect::<Vec<_>>(), [&1]);
+
+        let (ours, theirs) = bags();
+        let either = ours.symmetric_difference(theirs);
+        assert_eq!(either.as_inner().keys().collect::<Vec<_>>(), [&1, &3]);
+        assert_eq!(either[&3], ["e"]);
+    }
+
+    #[test]
+    fn labelled() {
+        let (ours, theirs) = bags();
+        let merged = ours.merge_labelled("here", theirs, "there");
+        assert_eq!(merged[&2], [("here", "b"), ("here", "c"), ("there", "d")]);
+        assert_eq!(merged[&3], [("there", "e")]);
+
+        let (ours, theirs) = bags();
+        let common = ours.intersection_labelled("here", theirs, "there");
+        assert_eq!(common.len(), 1);
+        assert_eq!(common[&2], [("here", "b"), ("here", "c"), ("there", "d")]);
+
+        let (ours, theirs) = bags();
+        let only_ours = ours.difference_labelled("here", &theirs);
+        assert_eq!(only_ours[&1], [("here", "a")]);
+
+        let (ours, theirs) = bags();
+        let either = ours.symmetric_difference_labelled("here", theirs, "there");
+        assert_eq!(either[&1], [("here", "a")]);
+        assert_eq!(either[&3], [("there", "e")]);
+    }
+}
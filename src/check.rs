@@ -0,0 +1,58 @@
+//! `yadf check`: the files of a manifest compared with their listed hashes,
+//! reported the way `sha256sum -c` does.
+
+use anyhow::Context;
+use std::io::{self, Write};
+use std::path::Path;
+use yadf::FileStatus;
+use yadf::{Algorithm, WithHasher};
+
+pub fn run(path: &Path, algorithm: &Algorithm) -> anyhow::Result<()> {
+    if !algorithm.is_stable() {
+        anyhow::bail!(
+            "{:?} hashes change from one run to the next, \
+            they can't be checked against a manifest",
+            algorithm
+        );
+    }
+    let context = || format!("reading the manifest: {:?}", path.display());
+    let file = std::fs::File::open(path).with_context(context)?;
+    let manifest = yadf::Manifest::read(io::BufReader::new(file)).with_context(context)?;
+    log::debug!("checking {} files with {:?}", manifest.len(), algorithm);
+    algorithm.dispatch(Check { manifest })
+}
+
+struct Check {
+    manifest: yadf::Manifest,
+}
+
+impl WithHasher for Check {
+    type Output = anyhow::Result<()>;
+
+    fn run<H>(self) -> anyhow::Result<()>
+    where
+        H: yadf::Hasher,
+        H::Hash: std::fmt::Debug + yadf::HashBytes,
+    {
+        let checked = self.manifest.check::<H>(yadf::default_io_threads());
+        let mut stdout = io::BufWriter::new(io::stdout().lock());
+        let mut failed = 0;
+        for (path, status) in &checked {
+            if *status != FileStatus::Unchanged {
+                failed += 1;
+            }
+            let status = match status {
+                FileStatus::Unchanged => "OK",
+                FileStatus::Modified => "MODIFIED",
+                FileStatus::Missing => "MISSING",
+                FileStatus::Unreadable => "UNREADABLE",
+            };
+            writeln!(stdout, "{}: {}", path.display(), status)?;
+        }
+        stdout.flush()?;
+        if failed > 0 {
+            anyhow::bail!("{} of {} files did not match", failed, checked.len());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+//! Whole-directory duplicates, derived bottom-up from a file-level
+//! [`TreeBag`].
+//!
+//! A directory is identified by its listing: the names of its files paired
+//! with their content hashes, and the names of its subdirectories paired with
+//! their own identities. Two directories with the same listing get the same
+//! identity, so a copied project tree collapses into a single group rather
+//! than thousands of file pairs.
+//!
+//! [`overlaps`] relaxes this for directories that are close but not equal:
+//! an old backup folder whose contents all live on in a newer one.
+
+mod overlap;
+
+pub use overlap::{overlaps, Overlap, Relation};
+
+use crate::{FileCounter, Path, TreeBag};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::ffi::OsStr;
+
+/// Directories grouped by their entire contents.
+///
+/// The key is an opaque identity, only meaningful within a single bag: two
+/// directories share one if and only if they hold the same names with the
+/// same contents, recursively.
+pub type DirCounter = TreeBag<usize, Path>;
+
+/// What a name in a directory listing refers to.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Entry<'a, K> {
+    File(&'a K),
+    Directory(usize),
+}
+
+/// A directory as seen from the scanned files: the files directly in it, and
+/// the subdirectories that lead to more.
+#[derive(Debug)]
+struct Node<'a, K> {
+    files: Vec<(&'a OsStr, &'a K)>,
+    directories: BTreeSet<&'a std::path::Path>,
+}
+
+impl<K> Default for Node<'_, K> {
+    fn default() -> Self {
+        Self {
+            files: Vec::new(),
+            directories: BTreeSet::new(),
+        }
+    }
+}
+
+/// Groups every directory under `roots` holding at least one scanned file by
+/// its contents, keeping only the highest directories of each match: when
+/// `a/` and `b/` are identical, `a/src/` and `b/src/` are implied and left
+/// out.
+///
+/// Only the files in `files` are considered. A filter that kept some files
+/// out of the scan (size bounds, name patterns, hard links) keeps them out of
+/// the comparison too, and empty directories are never seen at all.
+pub fn group<K, P>(files: &FileCounter<K>, roots: &[P]) -> DirCounter
+where
+    K: Ord,
+    P: AsRef<std::path::Path>,
+{
+    let tree = listings(files, roots);
+    let identities = identify(&tree);
+    let bag: TreeBag<usize, &std::path::Path> = identities
+        .iter()
+        .map(|(&directory, &identity)| (identity, directory))
+        .collect();
+    let duplicated: HashSet<usize> = bag
+        .duplicates()
+        .iter()
+        .flat_map(|bucket| bucket.first())
+        .map(|directory| identities[directory])
+        .collect();
+    // A group is implied by its parents' when every one of its directories
+    // sits in a parent of one and the same duplicated group: that group's
+    // match already covers it. Parents in different groups say nothing of
+    // one another, and this group is the only sign they share contents.
+    let is_implied = |bucket: &[&std::path::Path]| {
+        let mut parents = bucket
+            .iter()
+            .map(|directory| directory.parent().and_then(|parent| identities.get(parent)));
+        let first = parents.next().flatten();
+        first.is_some_and(|identity| duplicated.contains(identity))
+            && parents.all(|identity| identity == first)
+    };
+    bag.into_inner()
+        .into_iter()
+        .filter(|(_, bucket)| !is_implied(bucket))
+        .flat_map(|(identity, bucket)| {
+            bucket
+                .into_iter()
+                .map(move |directory| (identity, Path::from(directory)))
+        })
+        .collect()
+}
+
+/// Builds the listing of every directory between the scanned files and the
+/// root they were found under.
+fn listings<'a, K, P>(
+    files: &'a FileCounter<K>,
+    roots: &[P],
+) -> BTreeMap<&'a std::path::Path, Node<'a, K>>
+where
+    P: AsRef<std::path::Path>,
+{
+    let mut tree: BTreeMap<_, Node<'_, K>> = BTreeMap::new();
+    for (hash, bucket) in files.as_inner() {
+        for file in bucket {
+            let file = file.as_ref();
+            let mut chain = containing(file, roots);
+            let (Some(directory), Some(name)) = (chain.next(), file.file_name()) else {
+                continue;
+            };
+            tree.entry(directory).or_default().files.push((name, hash));
+            let mut child = directory;
+            for parent in chain {
+                tree.entry(parent).or_default().directories.insert(child);
+                child = parent;
+            }
+        }
+    }
+    tree
+}
+
+/// The directories `file` sits in, innermost first, up to and including the
+/// root it was found under. A file given directly as a root belongs to no
+/// directory.
+fn containing<'a, 'r, P>(
+    file: &'a std::path::Path,
+    roots: &'r [P],
+) -> impl Iterator<Item = &'a std::path::Path> + 'r
+where
+    'a: 'r,
+    P: AsRef<std::path::Path>,
+{
+    let is_root = |path: &std::path::Path| roots.iter().any(|root| root.as_ref() == path);
+    let first = if is_root(file) { None } else { file.parent() };
+    std::iter::successors(first, move |&directory| {
+        if is_root(directory) {
+            None
+        } else {
+            directory.parent()
+        }
+    })
+}
+
+/// Assigns each directory its identity, deepest first so every
+/// subdirectory's is known by the time its parent's listing is built.
+fn identify<'a, K>(
+    tree: &BTreeMap<&'a std::path::Path, Node<'a, K>>,
+) -> BTreeMap<&'a std::path::Path, usize>
+where
+    K: Ord,
+{
+    let mut order: Vec<_> = tree.keys().copied().collect();
+    order.sort_by_key(|directory| std::cmp::Reverse(directory.components().count()));
+    let mut interned: BTreeMap<Vec<(&OsStr, Entry<'_, K>)>, usize> = BTreeMap::new();
+    let mut identities = BTreeMap::new();
+    for directory in order {
+        let node = &tree[directory];
+        let mut listing: Vec<_> = node
+            .files
+            .iter()
+            .map(|&(name, hash)| (name, Entry::File(hash)))
+            .chain(node.directories.iter().filter_map(|&child| {
+                let name = child.file_name()?;
+                Some((name, Entry::Directory(identities[child])))
+            }))
+            .collect();
+        listing.sort();
+        let next = interned.len();
+        let identity = *interned.entry(listing).or_insert(next);
+        identities.insert(directory, identity);
+    }
+    identities
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counter(files: &[(u64, &str)]) -> FileCounter<u64> {
+        files
+            .iter()
+            .map(|&(hash, path)| (hash, Path::from(path)))
+            .collect()
+    }
+
+    fn groups(bag: &DirCounter) -> Vec<Vec<String>> {
+        bag.duplicates()
+            .iter()
+            .map(|bucket| {
+                bucket
+                    .iter()
+                    .map(|path| path.as_ref().display().to_string())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn only_the_highest_matching_directories_are_reported() {
+        let files = counter(&[
+            (1, "root/a/x"),
+            (2, "root/a/src/y"),
+            (1, "root/b/x"),
+            (2, "root/b/src/y"),
+            (3, "root/c/z"),
+        ]);
+        let bag = group(&files, &["root"]);
+        assert_eq!(groups(&bag), [["root/a", "root/b"]]);
+    }
+
+    #[test]
+    fn same_contents_under_different_names_differ() {
+        let files = counter(&[(1, "root/a/x"), (1, "root/b/renamed")]);
+        let bag = group(&files, &["root"]);
+        assert!(groups(&bag).is_empty());
+    }
+
+    #[test]
+    fn nested_matches_outside_a_matching_parent_are_kept() {
+        let files = counter(&[
+            (1, "root/a/src/y"),
+            (1, "root/b/src/y"),
+            (1, "root/c/src/y"),
+            (2, "root/c/extra"),
+        ]);
+        let bag = group(&files, &["root"]);
+        assert_eq!(
+            groups(&bag),
+            [
+                vec!["root/a/src", "root/b/src", "root/c/src"],
+                vec!["root/a", "root/b"],
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_under_parents_of_different_groups_are_kept() {
+        let files = counter(&[
+            (1, "root/a/x"),
+            (3, "root/a/src/y"),
+            (1, "root/b/x"),
+            (3, "root/b/src/y"),
+            (2, "root/c/z"),
+            (3, "root/c/src/y"),
+            (2, "root/d/z"),
+            (3, "root/d/src/y"),
+        ]);
+        let bag = group(&files, &["root"]);
+        assert_eq!(
+            groups(&bag),
+            [
+                vec!["root/a/src", "root/b/src", "root/c/src", "root/d/src"],
+                vec!["root/a", "root/b"],
+                vec!["root/c", "root/d"],
+            ]
+        );
+    }
+}
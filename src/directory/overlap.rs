@@ -0,0 +1,378 @@
+//! Directories that overlap without necessarily being identical: one holding
+//! everything another does, or the two sharing most of their contents.
+//!
+//! Names play no part here. A directory is reduced to the set of content
+//! hashes found anywhere under it, so a reorganised backup still shows up as
+//! containing the original.
+
+use super::containing;
+use crate::{FileCounter, Path};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+/// How the contents of two directories relate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Relation {
+    /// Both hold exactly the same contents, possibly under other names.
+    Equal,
+    /// Everything in the left directory is also somewhere in the right one.
+    Subset,
+    /// Neither holds everything the other does, but they are similar enough.
+    Similar,
+}
+
+/// Two unrelated directories (neither inside the other) sharing contents.
+#[derive(Debug)]
+pub struct Overlap {
+    pub left: Path,
+    pub right: Path,
+    pub relation: Relation,
+    /// Jaccard index of the two content sets: shared contents over all
+    /// contents, from 0 (nothing in common) to 1 (equal).
+    pub similarity: f64,
+}
+
+/// Every scanned directory, indexed, with what the overlap search needs to
+/// know about it.
+#[derive(Debug, Default)]
+struct Directories<'a> {
+    index: BTreeMap<&'a std::path::Path, usize>,
+    paths: Vec<&'a std::path::Path>,
+    parents: Vec<Option<usize>>,
+    /// Number of distinct contents anywhere under each directory.
+    sizes: Vec<usize>,
+    /// The contents with too many copies to pair up their holders found
+    /// under each directory, by increasing number.
+    crowded: Vec<Vec<usize>>,
+}
+
+impl<'a> Directories<'a> {
+    fn intern(&mut self, path: &'a std::path::Path) -> usize {
+        let next = self.paths.len();
+        *self.index.entry(path).or_insert_with(|| {
+            self.paths.push(path);
+            self.parents.push(None);
+            self.sizes.push(0);
+            self.crowded.push(Vec::new());
+            next
+        })
+    }
+
+    fn are_nested(&self, a: usize, b: usize) -> bool {
+        self.paths[a].starts_with(self.paths[b]) || self.paths[b].starts_with(self.paths[a])
+    }
+}
+
+/// The most copies of a single content whose holders [`overlaps`] pairs up
+/// one by one.
+const MAX_COPIES: usize = 256;
+
+/// Finds every pair of directories under `roots` where one holds all of the
+/// other's contents, or whose similarity is at least `threshold`. A
+/// threshold of 1 therefore only reports containment.
+///
+/// Like [`super::group`], only the highest pairs are kept: when an old backup
+/// is contained in a newer one, so is each of its subdirectories, and those
+/// implied pairs are left out.
+///
+/// Only contents found in more than one place can be shared, so only the
+/// duplicated buckets of `files` generate pairs; the rest just count towards
+/// the size of the directories they sit in. Contents with more than 256
+/// copies, boilerplate such as empty files or licenses, generate no pairs
+/// either, as pairing all of their holders would take quadratic time: they
+/// are only counted as shared by the pairs the other contents made.
+pub fn overlaps<K, P>(files: &FileCounter<K>, roots: &[P], threshold: f64) -> Vec<Overlap>
+where
+    P: AsRef<std::path::Path>,
+{
+    let mut directories = Directories::default();
+    let mut shared: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut crowded = 0;
+    for bucket in files.as_inner().values() {
+        let mut holders = BTreeSet::new();
+        for file in bucket {
+            let mut child = None;
+            for directory in containing(file.as_ref(), roots) {
+                let id = directories.intern(directory);
+                if let Some(child) = child {
+                    directories.parents[child] = Some(id);
+                }
+                holders.insert(id);
+                child = Some(id);
+            }
+        }
+        for &holder in &holders {
+            directories.sizes[holder] += 1;
+        }
+        if bucket.len() < 2 {
+            continue;
+        }
+        if bucket.len() > MAX_COPIES {
+            for &holder in &holders {
+                directories.crowded[holder].push(crowded);
+            }
+            crowded += 1;
+            continue;
+        }
+        for &a in &holders {
+            for &b in holders.range(a + 1..) {
+                if !directories.are_nested(a, b) {
+                    *shared.entry((a, b)).or_default() += 1;
+                }
+            }
+        }
+    }
+    for (&(a, b), count) in &mut shared {
+        *count += common(&directories.crowded[a], &directories.crowded[b]);
+    }
+    Search {
+        directories,
+        shared,
+        threshold,
+    }
+    .run()
+}
+
+/// How many numbers two increasing lists have in common.
+fn common(a: &[usize], b: &[usize]) -> usize {
+    let (mut a, mut b) = (a.iter().peekable(), b.iter().peekable());
+    let mut common = 0;
+    while let (Some(x), Some(y)) = (a.peek(), b.peek()) {
+        match x.cmp(y) {
+            std::cmp::Ordering::Less => {
+                a.next();
+            }
+            std::cmp::Ordering::Greater => {
+                b.next();
+            }
+            std::cmp::Ordering::Equal => {
+                common += 1;
+                a.next();
+                b.next();
+            }
+        }
+    }
+    common
+}
+
+/// The pairwise counts, and the rules deciding which pairs are worth
+/// reporting.
+struct Search<'a> {
+    directories: Directories<'a>,
+    shared: HashMap<(usize, usize), usize>,
+    threshold: f64,
+}
+
+impl Search<'_> {
+    fn run(self) -> Vec<Overlap> {
+        let mut children = vec![Vec::new(); self.directories.paths.len()];
+        for (child, parent) in self.directories.parents.iter().enumerate() {
+            if let Some(parent) = *parent {
+                children[parent].push(child);
+            }
+        }
+        let mut overlaps: Vec<_> = self
+            .shared
+            .keys()
+            .filter_map(|&(a, b)| {
+                let (left, right, relation) = self.relation(a, b)?;
+                let implied = match relation {
+                    Relation::Equal => {
+                        self.is_implied_subset(left, right, &children)
+                            || self.is_implied_subset(right, left, &children)
+                    }
+                    Relation::Subset => self.is_implied_subset(left, right, &children),
+                    Relation::Similar => self
+                        .parents(left, right)
+                        .is_some_and(|(left, right)| self.relation(left, right).is_some()),
+                };
+                (!implied).then(|| Overlap {
+                    left: self.directories.paths[left].into(),
+                    right: self.directories.paths[right].into(),
+                    relation,
+                    similarity: self.similarity(left, right),
+                })
+            })
+            .collect();
+        overlaps.sort_by(|a, b| {
+            b.similarity
+                .total_cmp(&a.similarity)
+                .then_with(|| a.left.as_ref().cmp(b.left.as_ref()))
+                .then_with(|| a.right.as_ref().cmp(b.right.as_ref()))
+        });
+        overlaps
+    }
+
+    fn shared(&self, a: usize, b: usize) -> Option<usize> {
+        self.shared.get(&(a.min(b), a.max(b))).copied()
+    }
+
+    fn similarity(&self, a: usize, b: usize) -> f64 {
+        let shared = self.shared(a, b).unwrap_or(0);
+        let total = self.directories.sizes[a] + self.directories.sizes[b] - shared;
+        shared as f64 / total as f64
+    }
+
+    /// Whether everything under `left` is also under `right`.
+    fn contains(&self, left: usize, right: usize) -> bool {
+        self.shared(left, right) == Some(self.directories.sizes[left])
+    }
+
+    /// The pair oriented so that a subset is on the left, and how it relates;
+    /// `None` if it isn't worth reporting at all.
+    fn relation(&self, a: usize, b: usize) -> Option<(usize, usize, Relation)> {
+        match (self.contains(a, b), self.contains(b, a)) {
+            (true, true) => Some((a, b, Relation::Equal)),
+            (true, false) => Some((a, b, Relation::Subset)),
+            (false, true) => Some((b, a, Relation::Subset)),
+            (false, false) => {
+                (self.similarity(a, b) >= self.threshold).then_some((a, b, Relation::Similar))
+            }
+        }
+    }
+
+    fn parents(&self, a: usize, b: usize) -> Option<(usize, usize)> {
+        Some((self.directories.parents[a]?, self.directories.parents[b]?))
+    }
+
+    /// Whether `left` being contained in `right` is already told by a higher
+    /// pair: their parents, `left`'s parent in `right`, or `left` in a
+    /// strictly smaller child of `right`.
+    fn is_implied_subset(&self, left: usize, right: usize, children: &[Vec<usize>]) -> bool {
+        let sizes = &self.directories.sizes;
+        self.parents(left, right)
+            .is_some_and(|(left, right)| self.contains(left, right))
+            || self.directories.parents[left].is_some_and(|parent| self.contains(parent, right))
+            || children[right]
+                .iter()
+                .any(|&child| sizes[child] < sizes[right] && self.contains(left, child))
+    }
+}
+
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Relation::Equal => "equal",
+            Relation::Subset => "subset",
+            Relation::Similar => "similar",
+        })
+    }
+}
+
+impl Serialize for Relation {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl Serialize for Overlap {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut overlap = serializer.serialize_struct("Overlap", 4)?;
+        overlap.serialize_field("relation", &self.relation)?;
+        overlap.serialize_field("similarity", &self.similarity)?;
+        overlap.serialize_field("left", &self.left)?;
+        overlap.serialize_field("right", &self.right)?;
+        overlap.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overlaps_of(files: &[(u64, &str)], threshold: f64) -> Vec<(String, Relation, String)> {
+        let files: FileCounter<u64> = files
+            .iter()
+            .map(|&(hash, path)| (hash, Path::from(path)))
+            .collect();
+        overlaps(&files, &["root"], threshold)
+            .into_iter()
+            .map(|overlap| {
+                (
+                    overlap.left.as_ref().display().to_string(),
+                    overlap.relation,
+                    overlap.right.as_ref().display().to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn an_old_backup_is_contained_in_the_new_one() {
+        let result = overlaps_of(
+            &[
+                (1, "root/old/a"),
+                (2, "root/old/docs/b"),
+                (1, "root/new/a"),
+                (2, "root/new/moved/b"),
+                (3, "root/new/c"),
+            ],
+            1.0,
+        );
+        assert_eq!(
+            result,
+            [("root/old".into(), Relation::Subset, "root/new".into())]
+        );
+    }
+
+    #[test]
+    fn similar_directories_above_the_threshold() {
+        let files = [
+            (1, "root/a/1"),
+            (2, "root/a/2"),
+            (3, "root/a/3"),
+            (4, "root/a/4"),
+            (1, "root/b/1"),
+            (2, "root/b/2"),
+            (3, "root/b/3"),
+            (5, "root/b/5"),
+        ];
+        assert_eq!(
+            overlaps_of(&files, 0.6),
+            [("root/a".into(), Relation::Similar, "root/b".into())]
+        );
+        assert!(overlaps_of(&files, 0.7).is_empty());
+    }
+
+    #[test]
+    fn contents_with_many_copies_still_count_as_shared() {
+        let paths: Vec<_> = (0..MAX_COPIES)
+            .map(|index| format!("root/{index}/common"))
+            .chain(["root/a/common".into(), "root/b/common".into()])
+            .collect();
+        let files: Vec<_> = paths
+            .iter()
+            .map(|path| (1, path.as_str()))
+            .chain([(2, "root/a/x"), (2, "root/b/x")])
+            .collect();
+        assert_eq!(
+            overlaps_of(&files, 1.0),
+            [("root/a".into(), Relation::Equal, "root/b".into())]
+        );
+    }
+
+    #[test]
+    fn contents_with_many_copies_still_count_towards_sizes() {
+        // Only the old backup holds these, so it can't be contained in the
+        // new one, however many copies there are.
+        let paths: Vec<_> = (0..MAX_COPIES + 44)
+            .map(|index| format!("root/old/{index}/x"))
+            .collect();
+        let files: Vec<_> = paths
+            .iter()
+            .map(|path| (1, path.as_str()))
+            .chain([(2, "root/old/y"), (2, "root/new/y")])
+            .collect();
+        assert_eq!(
+            overlaps_of(&files, 1.0),
+            [("root/new".into(), Relation::Subset, "root/old".into())]
+        );
+    }
+}
@@ -9,33 +9,155 @@ mod advise;
 mod file;
 pub mod filter;
 mod hash;
+pub mod os;
 mod pipeline;
 pub mod pool;
 mod prefetch;
+mod tracked;
+// The one module handing the kernel pointers to fill in on its own time.
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+#[allow(unsafe_code)]
+mod uring;
 
-use crate::ext::{IteratorExt, WalkBuilderAddPaths, WalkParallelForEach};
+use crate::ext::IteratorExt;
+use crate::handle::{Monitor, Phase};
+use crate::path::FileInfo;
 use crate::units::Bytes;
+use crate::vfs::{Entry, FileSystem, Visit};
 use crate::TreeBag;
 use pipeline::Sink;
 use prefetch::{Progress, Queue, Window};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use std::path::{Path, PathBuf};
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
+};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Every full content hash computed by a scan, for its [`crate::Manifest`].
+pub type FullHashes<K> = std::sync::Mutex<Vec<(K, std::path::PathBuf)>>;
 
 /// Files above this size get an extra 4 KiB tail-hash pass before a full
 /// read, to cheaply split apart large files that only share a header.
 const SUFFIX_HASH_THRESHOLD: Bytes = Bytes::kib(64);
 
+/// How many files the partial pass hands at once to a [`FileSystem`] which
+/// batches reads. Enough to keep its queue deep, few enough that every I/O
+/// thread gets batches of its own.
+const BATCH: usize = 1024;
+
+/// How the files to scan are found from the paths given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Depth {
+    /// The paths are the files: nothing is walked, and each path's metadata
+    /// is fetched once.
+    Exact,
+    /// Every file below the paths, at most this many levels down.
+    Below(Option<usize>),
+}
+
 /// A candidate file carried through the hashing pipeline together with its
 /// already-known size, so later stages never need to re-`stat` it.
 #[derive(Debug)]
 pub struct Candidate {
-    path: PathBuf,
+    path: crate::Path,
     size: Bytes,
 }
 
+impl Candidate {
+    /// A file of `size` bytes, for a stage of one's own.
+    pub fn new(path: impl Into<crate::Path>, size: u64) -> Self {
+        Self {
+            path: path.into(),
+            size: Bytes::new(size),
+        }
+    }
+
+    pub fn path(&self) -> &crate::Path {
+        &self.path
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size.get()
+    }
+
+    pub fn into_path(self) -> crate::Path {
+        self.path
+    }
+}
+
 impl AsRef<Path> for Candidate {
     fn as_ref(&self) -> &Path {
-        &self.path
+        self.path.as_ref()
+    }
+}
+
+/// As `{"path": ..., "size": ...}`, so the bags between the stages of a scan
+/// can be saved and picked up again.
+impl serde::Serialize for Candidate {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+        let mut candidate = serializer.serialize_struct("Candidate", 2)?;
+        candidate.serialize_field("path", &self.path)?;
+        candidate.serialize_field("size", &self.size.get())?;
+        candidate.end()
+    }
+}
+
+/// Reads a candidate back from its serialized form. What the walk learned
+/// about the file, besides its size, isn't part of it.
+impl<'de> serde::Deserialize<'de> for Candidate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_struct("Candidate", &["path", "size"], CandidateVisitor)
+    }
+}
+
+struct CandidateVisitor;
+
+impl<'de> serde::de::Visitor<'de> for CandidateVisitor {
+    type Value = Candidate;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str("a path and a size")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Candidate, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        use serde::de::Error;
+        let path: crate::Path = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(0, &self))?;
+        let size: u64 = seq
+            .next_element()?
+            .ok_or_else(|| A::Error::invalid_length(1, &self))?;
+        Ok(Candidate::new(path, size))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Candidate, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        use serde::de::Error;
+        let (mut path, mut size) = (None, None);
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "path" => path = Some(map.next_value::<crate::Path>()?),
+                "size" => size = Some(map.next_value::<u64>()?),
+                _ => {
+                    map.next_value::<serde::de::IgnoredAny>()?;
+                }
+            }
+        }
+        let path = path.ok_or_else(|| A::Error::missing_field("path"))?;
+        let size = size.ok_or_else(|| A::Error::missing_field("size"))?;
+        Ok(Candidate::new(path, size))
     }
 }
 
@@ -45,47 +167,270 @@ impl AsRef<Path> for Candidate {
 /// metadata the walk already fetches, at no extra syscall cost), then only
 /// opens files that share a size with at least one other file: a file with
 /// a unique size can never be a duplicate, so it is never read.
+///
+/// With `keep_info`, every file carries the metadata the walk fetched for it
+/// all the way to the final bag.
 pub fn find_dupes_partial<H, P>(
+    fs: &dyn FileSystem,
     directories: &[P],
-    max_depth: Option<usize>,
+    depth: Depth,
     filter: filter::FileFilter,
+    keep_info: bool,
     io_threads: usize,
+    monitor: &Monitor,
 ) -> TreeBag<H::Hash, Candidate>
 where
     H: crate::hasher::Hasher,
     P: AsRef<Path>,
 {
-    let by_size = collect_by_size(directories, max_depth, &filter);
-    // Only files sharing a size get opened, so only those are worth warming.
-    let queue = Queue::covering(&by_size, |_| hash::BLOCK);
-    pool::install(io_threads, || {
-        queue.warm(Window::PARTIAL, |progress| {
-            partial_hash_by_size::<H>(by_size, progress)
-        })
-    })
+    let by_size = collect_by_size(fs, directories, depth, &filter, keep_info, monitor);
+    hash_by_size::<H>(fs, by_size, io_threads, monitor)
+}
+
+/// What [`missing_from`] found out about the source files.
+#[derive(Debug, Default)]
+pub struct Missing {
+    /// The source files whose content is found nowhere at the destinations.
+    pub missing: Vec<crate::Path>,
+    /// The source files which couldn't be read, and may or may not have a
+    /// copy at the destinations.
+    pub unverified: Vec<crate::Path>,
+}
+
+/// Every file under `sources` whose content is found nowhere under
+/// `destinations`, and every one which couldn't be compared.
+///
+/// Each side is walked with a filter of its own, so a destination file
+/// hard-linked to a source file still counts as its copy. Sizes are matched
+/// before anything is read: a source file whose size appears nowhere at the
+/// destinations is missing outright, and destination files of a size absent
+/// from the sources are never opened.
+///
+/// A source file failing any of its reads is unverified rather than missing
+/// or copied: it drops out of the comparison, or keeps a hash covering only
+/// part of it, which could match a different destination file.
+pub fn missing_from<H, P, Q>(
+    fs: &dyn FileSystem,
+    sources: &[P],
+    destinations: &[Q],
+    sources_depth: Depth,
+    destinations_depth: Depth,
+    filter: impl Fn() -> filter::FileFilter,
+    io_threads: usize,
+) -> Missing
+where
+    H: crate::hasher::Hasher,
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let monitor = Monitor::default();
+    let by_size = collect_by_size(fs, sources, sources_depth, &filter(), false, &monitor);
+    let mut at_destinations = collect_by_size(
+        fs,
+        destinations,
+        destinations_depth,
+        &filter(),
+        false,
+        &monitor,
+    )
+    .into_inner();
+    let mut missing = Vec::new();
+    let mut copies = HashSet::new();
+    let mut sources = HashSet::new();
+    let mut shared_sizes = TreeBag::default();
+    for (size, mut bucket) in by_size.into_inner() {
+        match at_destinations.remove(&size) {
+            None => missing.extend(bucket),
+            Some(destination_bucket) => {
+                sources.extend(bucket.iter().map(|path| path.as_ref().to_path_buf()));
+                copies.extend(
+                    destination_bucket
+                        .iter()
+                        .map(|path| path.as_ref().to_path_buf()),
+                );
+                bucket.extend(destination_bucket);
+                shared_sizes.as_inner_mut().insert(size, bucket);
+            }
+        }
+    }
+    let tracked = tracked::Tracked::new(fs);
+    let shared_sizes = hash_by_size::<H>(&tracked, shared_sizes, io_threads, &monitor);
+    let bag = dedupe::<H>(&tracked, shared_sizes, None, io_threads, &monitor);
+    let failed = tracked.into_failed();
+    for bucket in bag.into_inner().into_values() {
+        if !bucket.iter().any(|path| copies.contains(path.as_ref())) {
+            missing.extend(bucket);
+        }
+    }
+    missing.retain(|path| !failed.contains(path.as_ref()));
+    missing.sort_by(|a, b| a.as_ref().cmp(b.as_ref()));
+    let mut unverified: Vec<_> = failed.intersection(&sources).cloned().collect();
+    unverified.sort();
+    Missing {
+        missing,
+        unverified: unverified.into_iter().map(crate::Path::from).collect(),
+    }
+}
+
+/// Every file under `directories` with the same content as one of
+/// `queries`, grouped with it.
+///
+/// Only the queries and the walked files sharing a size with one of them are
+/// ever read; the rest of the tree is merely walked. Buckets not holding a
+/// query are dropped, and each query comes first in its own bucket.
+pub fn copies_of<H, P, Q>(
+    fs: &dyn FileSystem,
+    queries: &[Q],
+    directories: &[P],
+    depth: Depth,
+    filter: filter::FileFilter,
+    io_threads: usize,
+) -> crate::FileCounter<H::Hash>
+where
+    H: crate::hasher::Hasher,
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let mut wanted = TreeBag::default();
+    let mut canonical = HashSet::new();
+    for query in queries {
+        let query = query.as_ref();
+        match fs.metadata(query) {
+            Ok(meta) if meta.is_file => {
+                wanted.extend([(Bytes::new(meta.len), crate::Path::from(query))]);
+                canonical.extend(fs.canonicalize(query).ok());
+            }
+            Ok(_) => log::error!("{:?} is not a file", query),
+            Err(error) => log::error!("{}, couldn't get metadata for {:?}", error, query),
+        }
+    }
+    if wanted.is_empty() {
+        return TreeBag::default();
+    }
+    let monitor = Monitor::default();
+    let mut by_size =
+        collect_by_size(fs, directories, depth, &filter, false, &monitor).into_inner();
+    for (size, bucket) in wanted.as_inner_mut() {
+        if let Some(found) = by_size.remove(size) {
+            // A query sitting under one of the directories is not a copy of
+            // itself.
+            bucket.extend(found.into_iter().filter(|path| {
+                fs.canonicalize(path.as_ref())
+                    .map_or(true, |path| !canonical.contains(&path))
+            }));
+        }
+    }
+    let is_query = |path: &crate::Path| queries.iter().any(|query| query.as_ref() == path.as_ref());
+    let wanted = hash_by_size::<H>(fs, wanted, io_threads, &monitor);
+    let mut bag = dedupe::<H>(fs, wanted, None, io_threads, &monitor);
+    bag.as_inner_mut().retain(|_, bucket| {
+        bucket.sort_by_key(|path| !is_query(path));
+        bucket.first().is_some_and(is_query)
+    });
+    bag
 }
 
 /// Rehashes every bucket with more than one candidate to confirm (or rule
 /// out) a real content match; buckets already known to be unique are
 /// passed through untouched.
+///
+/// Each full content hash computed on the way is also added to `full`, if
+/// given.
 pub fn dedupe<H>(
+    fs: &dyn FileSystem,
     tree: TreeBag<H::Hash, Candidate>,
+    full: Option<&FullHashes<H::Hash>>,
     io_threads: usize,
+    monitor: &Monitor,
 ) -> crate::FileCounter<H::Hash>
 where
     H: crate::hasher::Hasher,
 {
+    monitor.start(Phase::Full, tree.as_inner().values().map(Vec::len).sum());
     let queue = Queue::covering(&tree, |candidate| {
         candidate.size.min(prefetch::CONTENT_HEAD)
     });
     pool::install(io_threads, || {
-        queue.warm(Window::CONTENT, |progress| {
+        queue.warm(fs, Window::CONTENT, |progress| {
             pipeline::collect(|sink| {
-                tree.into_inner().into_par_iter().for_each_with(
+                let buckets: Vec<_> = tree.into_inner().into_iter().collect();
+                buckets.into_par_iter().enumerate().for_each_with(
                     sink,
-                    |sink, bucket: (H::Hash, Vec<Candidate>)| {
+                    |sink, (index, bucket): (usize, (H::Hash, Vec<Candidate>))| {
                         let read = bucket.1.len();
-                        process_bucket::<H>(sink, bucket);
+                        if !monitor.should_stop() {
+                            process_bucket::<H>(fs, sink, index, bucket, full);
+                        }
+                        progress.advance(read);
+                        monitor.advance(read);
+                    },
+                )
+            })
+        })
+    })
+}
+
+/// The suffix pass of [`dedupe`] on its own: every bucket of more than one
+/// candidate is split by the hash of their last 4 KiB, save for the
+/// candidates too small for it to be worth a read, which keep their hash.
+pub fn suffix_pass<H>(
+    fs: &dyn FileSystem,
+    tree: TreeBag<H::Hash, Candidate>,
+    io_threads: usize,
+) -> TreeBag<H::Hash, Candidate>
+where
+    H: crate::hasher::Hasher,
+{
+    let buckets: Vec<_> = tree.into_inner().into_iter().collect();
+    pool::install(io_threads, || {
+        pipeline::collect(|sink| {
+            buckets.into_par_iter().enumerate().for_each_with(
+                sink,
+                |sink, (index, (old_hash, bucket))| {
+                    if bucket.len() == 1 {
+                        sink.send(old_hash, bucket.into_iter().next().unwrap());
+                        return;
+                    }
+                    let (large, rest): (Vec<_>, Vec<_>) = bucket
+                        .into_iter()
+                        .partition(|candidate| candidate.size >= SUFFIX_HASH_THRESHOLD);
+                    for candidate in rest {
+                        sink.send(old_hash, candidate);
+                    }
+                    let split = split_by_suffix::<H>(fs, index, old_hash, large);
+                    for (hash, group) in split.into_inner() {
+                        for candidate in group {
+                            sink.send(hash, candidate);
+                        }
+                    }
+                },
+            )
+        })
+    })
+}
+
+/// The full pass of [`dedupe`] on its own: every candidate of a bucket of
+/// more than one is read in full, save for those under 4 KiB, whose hash
+/// already covers their whole content.
+pub fn full_pass<H>(
+    fs: &dyn FileSystem,
+    tree: TreeBag<H::Hash, Candidate>,
+    io_threads: usize,
+) -> crate::FileCounter<H::Hash>
+where
+    H: crate::hasher::Hasher,
+{
+    let queue = Queue::covering(&tree, |candidate| {
+        candidate.size.min(prefetch::CONTENT_HEAD)
+    });
+    pool::install(io_threads, || {
+        queue.warm(fs, Window::CONTENT, |progress| {
+            pipeline::collect(|sink| {
+                tree.into_inner().into_par_iter().for_each_with(
+                    sink,
+                    |sink, group: (H::Hash, Vec<Candidate>)| {
+                        let read = group.1.len();
+                        hash_group::<H>(fs, sink, group, None);
                         progress.advance(read);
                     },
                 )
@@ -94,60 +439,455 @@ where
     })
 }
 
-/// Walks `directories` and groups every matching file by its size.
-fn collect_by_size<P>(
+/// Every file under `directories` whose full content hash is in `known`,
+/// grouped by that hash.
+///
+/// No size shortcut applies here, since a checksum list says nothing about
+/// sizes: every walked file is read in full.
+pub fn known_files<H, P>(
+    fs: &dyn FileSystem,
+    directories: &[P],
+    depth: Depth,
+    filter: filter::FileFilter,
+    known: &crate::KnownHashes,
+    io_threads: usize,
+) -> crate::FileCounter<H::Hash>
+where
+    H: crate::hasher::Hasher,
+    H::Hash: crate::hasher::HashBytes,
+    P: AsRef<Path>,
+{
+    full_hashes::<H, _>(
+        fs,
+        directories,
+        depth,
+        filter,
+        |hash| known.contains(hash),
+        io_threads,
+    )
+}
+
+/// Every file under `directories` for which `keep` accepts its full content
+/// hash, grouped by that hash. Every walked file is read in full.
+pub fn full_hashes<H, P>(
+    fs: &dyn FileSystem,
+    directories: &[P],
+    depth: Depth,
+    filter: filter::FileFilter,
+    keep: impl Fn(&H::Hash) -> bool + Sync,
+    io_threads: usize,
+) -> crate::FileCounter<H::Hash>
+where
+    H: crate::hasher::Hasher,
+    P: AsRef<Path>,
+{
+    hash_every_file(
+        fs,
+        directories,
+        depth,
+        filter,
+        |candidate| hash::full::<H>(fs, candidate.as_ref()),
+        keep,
+        io_threads,
+    )
+}
+
+/// Every file under `directories` under the hash a duplicate group holding
+/// it would be keyed by, like [`group_keys`] but walking for the files.
+pub fn walked_group_keys<H, P>(
+    fs: &dyn FileSystem,
+    directories: &[P],
+    depth: Depth,
+    filter: filter::FileFilter,
+    io_threads: usize,
+) -> crate::FileCounter<H::Hash>
+where
+    H: crate::hasher::Hasher,
+    P: AsRef<Path>,
+{
+    hash_every_file(
+        fs,
+        directories,
+        depth,
+        filter,
+        |candidate| {
+            if candidate.size < hash::BLOCK {
+                hash::partial::<H>(fs, candidate.as_ref(), candidate.size)
+            } else {
+                hash::full::<H>(fs, candidate.as_ref())
+            }
+        },
+        |_| true,
+        io_threads,
+    )
+}
+
+/// Every file under `directories` hashed by `hash`, those `keep` accepts
+/// grouped by that hash.
+fn hash_every_file<K, P>(
+    fs: &dyn FileSystem,
+    directories: &[P],
+    depth: Depth,
+    filter: filter::FileFilter,
+    hash: impl Fn(&Candidate) -> std::io::Result<K> + Sync,
+    keep: impl Fn(&K) -> bool + Sync,
+    io_threads: usize,
+) -> crate::FileCounter<K>
+where
+    K: Ord + Send,
+    P: AsRef<Path>,
+{
+    let by_size = collect_by_size(fs, directories, depth, &filter, false, &Monitor::default());
+    // One bucket of everything: every file is read, so every file is worth
+    // warming.
+    let files: TreeBag<(), Candidate> = by_size
+        .into_inner()
+        .into_iter()
+        .flat_map(|(size, bucket)| {
+            bucket
+                .into_iter()
+                .map(move |path| ((), Candidate { path, size }))
+        })
+        .collect();
+    let queue = Queue::covering(&files, |candidate| {
+        candidate.size.min(prefetch::CONTENT_HEAD)
+    });
+    pool::install(io_threads, || {
+        queue.warm(fs, Window::CONTENT, |progress| {
+            pipeline::collect(|sink| {
+                let files = files.into_inner().remove(&()).unwrap_or_default();
+                files
+                    .into_par_iter()
+                    .for_each_with(sink, |sink, candidate| {
+                        match hash(&candidate) {
+                            Ok(hash) if keep(&hash) => sink.send(hash, candidate.path),
+                            Ok(_) => {}
+                            Err(error) => {
+                                log::error!("{}, couldn't hash {:?}", error, candidate.as_ref())
+                            }
+                        }
+                        progress.advance(1);
+                    })
+            })
+        })
+    })
+}
+
+/// Every one of `files` under the hash a duplicate group holding it would be
+/// keyed by, without walking anything or grouping by size first.
+pub fn group_keys<H, P>(
+    fs: &dyn FileSystem,
+    files: &[P],
+    io_threads: usize,
+) -> crate::FileCounter<H::Hash>
+where
+    H: crate::hasher::Hasher,
+    P: AsRef<Path> + Sync,
+{
+    pool::install(io_threads, || {
+        pipeline::collect(|sink| {
+            files.par_iter().for_each_with(sink, |sink, path| {
+                let path = path.as_ref();
+                match hash::group_key::<H>(fs, path) {
+                    Ok(hash) => sink.send(hash, crate::Path::from(path)),
+                    Err(error) => log::error!("{}, couldn't hash {:?}", error, path),
+                }
+            })
+        })
+    })
+}
+
+/// Moves every file of `bag` without a duplicate under the hash a group
+/// holding it would be keyed by, rather than the cheaper one which told it
+/// apart from the other files. A file which can't be read is left out.
+pub fn hash_singletons<H>(
+    fs: &dyn FileSystem,
+    bag: crate::FileCounter<H::Hash>,
+    io_threads: usize,
+) -> crate::FileCounter<H::Hash>
+where
+    H: crate::hasher::Hasher,
+{
+    let (singletons, mut bag): (std::collections::BTreeMap<_, _>, _) = bag
+        .into_inner()
+        .into_iter()
+        .partition(|(_, bucket)| bucket.len() == 1);
+    let hashed: crate::FileCounter<H::Hash> = pool::install(io_threads, || {
+        pipeline::collect(|sink| {
+            singletons
+                .into_par_iter()
+                .flat_map_iter(|(_, bucket)| bucket)
+                .for_each_with(sink, |sink, file| {
+                    let path = file.as_ref();
+                    match hash::group_key::<H>(fs, path) {
+                        Ok(hash) => sink.send(hash, file),
+                        Err(error) => log::error!("{}, couldn't hash {:?}", error, path),
+                    }
+                })
+        })
+    });
+    for (hash, files) in hashed.into_inner() {
+        bag.entry(hash).or_default().extend(files);
+    }
+    bag.into()
+}
+
+/// Drops every duplicate group whose content is in `known`.
+///
+/// A group of files at least [`hash::BLOCK`] long is already keyed by its
+/// full content hash. Smaller files are keyed by a hash that also covers
+/// their size, so one of them is hashed again, which costs a single short
+/// read per group.
+pub fn drop_known<H>(
+    fs: &dyn FileSystem,
+    bag: crate::FileCounter<H::Hash>,
+    known: &crate::KnownHashes,
+    io_threads: usize,
+) -> crate::FileCounter<H::Hash>
+where
+    H: crate::hasher::Hasher,
+    H::Hash: crate::hasher::HashBytes,
+{
+    let is_known = |(hash, bucket): &(H::Hash, Vec<crate::Path>)| {
+        let Some(first) = bucket.first().filter(|_| bucket.len() > 1) else {
+            return false;
+        };
+        if known.contains(hash) {
+            return true;
+        }
+        let first = first.as_ref();
+        let is_small = fs
+            .metadata(first)
+            .is_ok_and(|meta| meta.len < hash::BLOCK.get());
+        is_small
+            && hash::full::<H>(fs, first)
+                .map_err(|error| log::error!("{}, couldn't hash {:?}", error, first))
+                .is_ok_and(|hash| known.contains(&hash))
+    };
+    let kept: std::collections::BTreeMap<_, _> = pool::install(io_threads, || {
+        bag.into_inner()
+            .into_par_iter()
+            .filter(|bucket| !is_known(bucket))
+            .collect()
+    });
+    kept.into()
+}
+
+/// Hashes every file listed in `manifest` in full and compares it with the
+/// listed digest.
+pub fn check<'a, H>(
+    fs: &dyn FileSystem,
+    manifest: &'a crate::Manifest,
+    io_threads: usize,
+) -> Vec<(&'a Path, crate::manifest::FileStatus)>
+where
+    H: crate::hasher::Hasher,
+    H::Hash: crate::hasher::HashBytes,
+{
+    use crate::hasher::HashBytes;
+    use crate::manifest::FileStatus;
+    let entries: Vec<_> = manifest.iter().collect();
+    let files: TreeBag<(), &Path> = entries.iter().map(|&(_, path)| ((), path)).collect();
+    let queue = Queue::covering(&files, |_| prefetch::CONTENT_HEAD);
+    pool::install(io_threads, || {
+        queue.warm(fs, Window::CONTENT, |progress| {
+            entries
+                .into_par_iter()
+                .map(|(digest, path)| {
+                    let status = match hash::full::<H>(fs, path) {
+                        Ok(hash) if hash.to_bytes() == digest => FileStatus::Unchanged,
+                        Ok(_) => FileStatus::Modified,
+                        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                            FileStatus::Missing
+                        }
+                        Err(error) => {
+                            log::error!("{}, couldn't hash {:?}", error, path);
+                            FileStatus::Unreadable
+                        }
+                    };
+                    progress.advance(1);
+                    (path, status)
+                })
+                .collect()
+        })
+    })
+}
+
+/// Partial-hashes every size bucket holding more than one file.
+pub fn hash_by_size<H>(
+    fs: &dyn FileSystem,
+    by_size: TreeBag<Bytes, crate::Path>,
+    io_threads: usize,
+    monitor: &Monitor,
+) -> TreeBag<H::Hash, Candidate>
+where
+    H: crate::hasher::Hasher,
+{
+    monitor.start(
+        Phase::Partial,
+        by_size.as_inner().values().map(Vec::len).sum(),
+    );
+    if fs.batches_reads() {
+        return pool::install(io_threads, || {
+            batched_hash_by_size::<H>(fs, by_size, monitor)
+        });
+    }
+    // Only files sharing a size get opened, so only those are worth warming.
+    let queue = Queue::covering(&by_size, |_| hash::BLOCK);
+    pool::install(io_threads, || {
+        queue.warm(fs, Window::PARTIAL, |progress| {
+            partial_hash_by_size::<H>(fs, by_size, progress, monitor)
+        })
+    })
+}
+
+/// Finds the files under `directories` as `depth` says and groups every
+/// matching one by its size, with its metadata if `keep_info`. The search
+/// stops early if `monitor` is cancelled.
+pub fn collect_by_size<P>(
+    fs: &dyn FileSystem,
     directories: &[P],
-    max_depth: Option<usize>,
+    depth: Depth,
     filter: &filter::FileFilter,
-) -> TreeBag<Bytes, PathBuf>
+    keep_info: bool,
+    monitor: &Monitor,
+) -> TreeBag<Bytes, crate::Path>
 where
     P: AsRef<Path>,
 {
-    let mut paths = directories
-        .iter()
-        .unique_by(|path| dunce::canonicalize(path).ok());
-    let first = paths.next().expect("there should be at least one path");
-    let walker = ignore::WalkBuilder::new(first)
-        .add_paths(paths)
-        .standard_filters(false)
-        .max_depth(max_depth)
-        .threads(num_cpus::get())
-        .build_parallel();
+    let roots: Vec<&Path> = directories.iter().map(AsRef::as_ref).collect();
+    let max_depth = match depth {
+        Depth::Exact => return collect_listed(fs, &roots, filter, keep_info, monitor),
+        Depth::Below(max_depth) => max_depth,
+    };
+    let info_roots = keep_info.then_some(roots.as_slice());
     pipeline::collect(|sink| {
-        let sink = &sink;
-        walker.for_each(|entry| {
+        fs.walk(&roots, max_depth, &|entry| {
+            let mut next = Visit::Continue;
             match entry {
                 Err(error) => log::error!("{}", error),
+                Ok(entry) if entry.metadata.is_dir => {
+                    if !filter.descends_into(&entry.path, &entry.metadata) {
+                        next = Visit::Skip;
+                    }
+                }
                 Ok(entry) => {
-                    if let Some((size, path)) = size_entry(filter, entry) {
+                    let root = |path: &Path| info_roots.map(|roots| root_index(path, roots));
+                    if let Some((size, path)) = size_entry(filter, entry, root) {
                         sink.send(size, path);
+                        monitor.found();
                     }
                 }
             }
-            ignore::WalkState::Continue
+            if monitor.should_stop() {
+                Visit::Quit
+            } else {
+                next
+            }
         })
     })
 }
 
-fn size_entry(filter: &filter::FileFilter, entry: ignore::DirEntry) -> Option<(Bytes, PathBuf)> {
-    let path = entry.path();
-    let meta = entry
-        .metadata()
-        .map_err(|error| log::error!("{}, couldn't get metadata for {:?}", error, path))
-        .ok()?;
-    let size = Bytes::new(meta.len());
-    if !filter.is_match(path, meta) {
+/// Groups the matching files of `files` by their size, fetching the
+/// metadata of each exactly once.
+///
+/// A file listed twice, under any names, is only kept once. Links are seen
+/// as links, the way a walk sees them, and left out with everything else
+/// which isn't a file.
+fn collect_listed(
+    fs: &dyn FileSystem,
+    files: &[&Path],
+    filter: &filter::FileFilter,
+    keep_info: bool,
+    monitor: &Monitor,
+) -> TreeBag<Bytes, crate::Path> {
+    let files: Vec<(usize, &Path)> = files
+        .iter()
+        .copied()
+        .enumerate()
+        .unique_by(|&(_, path)| resolve_parent(fs, path))
+        .collect();
+    pipeline::collect(|sink| {
+        files
+            .into_par_iter()
+            .for_each_with(sink, |sink, (index, path)| {
+                if monitor.should_stop() {
+                    return;
+                }
+                let metadata = match fs.symlink_metadata(path) {
+                    Ok(metadata) => metadata,
+                    Err(error) => {
+                        return log::error!("{}, couldn't get metadata for {:?}", error, path)
+                    }
+                };
+                let entry = Entry {
+                    path: path.to_path_buf(),
+                    metadata,
+                };
+                let root = |_: &Path| keep_info.then_some(index);
+                if let Some((size, path)) = size_entry(filter, entry, root) {
+                    sink.send(size, path);
+                    monitor.found();
+                }
+            })
+    })
+}
+
+/// Turns a found entry into a size-bucket item, or `None` if the filter
+/// rejects it. `root` tells which of the roots it was found under, for its
+/// [`FileInfo`], if that is wanted.
+fn size_entry(
+    filter: &filter::FileFilter,
+    entry: Entry,
+    root: impl FnOnce(&Path) -> Option<usize>,
+) -> Option<(Bytes, crate::Path)> {
+    let Entry { path, metadata } = entry;
+    if !filter.is_match(&path, &metadata) {
         return None;
     }
-    Some((size, entry.into_path()))
+    let size = Bytes::new(metadata.len);
+    Some(match root(&path) {
+        Some(root) => {
+            let info = FileInfo::new(&metadata, root);
+            (size, crate::Path::with_info(path, info))
+        }
+        None => (size, path.into()),
+    })
+}
+
+/// `path` with every directory leading to it resolved, but not its last
+/// component: the same for two names of one file, yet not for a link and
+/// what it points to. `path` itself if a directory can't be resolved.
+fn resolve_parent(fs: &dyn FileSystem, path: &Path) -> std::path::PathBuf {
+    let resolved = path.file_name().and_then(|name| {
+        let parent = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty());
+        let parent = fs.canonicalize(parent.unwrap_or(Path::new("."))).ok()?;
+        Some(parent.join(name))
+    });
+    resolved.unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Index of the root `path` was found under; the innermost one if roots are
+/// nested.
+fn root_index(path: &Path, roots: &[&Path]) -> usize {
+    roots
+        .iter()
+        .enumerate()
+        .filter(|(_, root)| path.starts_with(root))
+        .max_by_key(|(_, root)| root.components().count())
+        .map_or(0, |(index, _)| index)
 }
 
 /// Turns size-buckets into partial-hash buckets. Files that are the only
 /// one of their size are never opened; the rest are read for their first
 /// 4 KiB.
 fn partial_hash_by_size<H>(
-    by_size: TreeBag<Bytes, PathBuf>,
+    fs: &dyn FileSystem,
+    by_size: TreeBag<Bytes, crate::Path>,
     progress: &Progress,
+    monitor: &Monitor,
 ) -> TreeBag<H::Hash, Candidate>
 where
     H: crate::hasher::Hasher,
@@ -155,18 +895,70 @@ where
     pipeline::collect(|sink| {
         by_size.into_inner().into_par_iter().for_each_with(
             sink,
-            |sink, bucket: (Bytes, Vec<PathBuf>)| {
+            |sink, bucket: (Bytes, Vec<crate::Path>)| {
                 let read = bucket.1.len();
-                hash_size_bucket::<H>(sink, bucket);
+                if !monitor.should_stop() {
+                    hash_size_bucket::<H>(fs, sink, bucket);
+                }
                 progress.advance(read);
+                monitor.advance(read);
             },
         )
     })
 }
 
-fn hash_size_bucket<H>(sink: &Sink<H::Hash, Candidate>, (size, bucket): (Bytes, Vec<PathBuf>))
+/// [`partial_hash_by_size`] for a [`FileSystem`] which batches reads: the
+/// files sharing a size are read [`BATCH`] at a time, whatever their bucket,
+/// and nothing is prefetched.
+fn batched_hash_by_size<H>(
+    fs: &dyn FileSystem,
+    by_size: TreeBag<Bytes, crate::Path>,
+    monitor: &Monitor,
+) -> TreeBag<H::Hash, Candidate>
 where
     H: crate::hasher::Hasher,
+{
+    pipeline::collect(|sink| {
+        let mut batches: Vec<Vec<Candidate>> = Vec::new();
+        for (size, bucket) in by_size.into_inner() {
+            if bucket.len() == 1 {
+                let path = bucket.into_iter().next().unwrap();
+                sink.send(hash::size_only::<H>(size), Candidate { path, size });
+                monitor.advance(1);
+                continue;
+            }
+            for path in bucket {
+                if batches.last().is_none_or(|batch| batch.len() == BATCH) {
+                    batches.push(Vec::new());
+                }
+                batches.last_mut().unwrap().push(Candidate { path, size });
+            }
+        }
+        batches.into_par_iter().for_each_with(sink, |sink, batch| {
+            let read = batch.len();
+            if !monitor.should_stop() {
+                let files: Vec<_> = batch.iter().map(|c| (c.as_ref(), c.size)).collect();
+                let hashes = hash::partials::<H>(fs, &files);
+                for (candidate, hash) in batch.into_iter().zip(hashes) {
+                    match hash {
+                        Ok(hash) => sink.send(hash, candidate),
+                        Err(error) => {
+                            log::error!("{}, couldn't hash {:?}", error, candidate.as_ref())
+                        }
+                    }
+                }
+            }
+            monitor.advance(read);
+        })
+    })
+}
+
+fn hash_size_bucket<H>(
+    fs: &dyn FileSystem,
+    sink: &Sink<H::Hash, Candidate>,
+    (size, bucket): (Bytes, Vec<crate::Path>),
+) where
+    H: crate::hasher::Hasher,
 {
     if bucket.len() == 1 {
         let path = bucket.into_iter().next().unwrap();
@@ -176,7 +968,7 @@ where
     bucket
         .into_par_iter()
         .for_each_with(sink.clone(), |sink, path| {
-            match hash::partial::<H>(&path, size) {
+            match hash::partial::<H>(fs, path.as_ref(), size) {
                 Ok(hash) => sink.send(hash, Candidate { path, size }),
                 Err(error) => log::error!("{}, couldn't hash {:?}", error, path),
             }
@@ -184,14 +976,17 @@ where
 }
 
 fn process_bucket<H>(
+    fs: &dyn FileSystem,
     sink: &Sink<H::Hash, crate::Path>,
+    index: usize,
     (old_hash, bucket): (H::Hash, Vec<Candidate>),
+    full: Option<&FullHashes<H::Hash>>,
 ) where
     H: crate::hasher::Hasher,
 {
     if bucket.len() == 1 {
         let candidate = bucket.into_iter().next().unwrap();
-        sink.send(old_hash, candidate.path.into());
+        sink.send(old_hash, candidate.path);
         return;
     }
     let (large, rest): (Vec<_>, Vec<_>) = bucket
@@ -200,48 +995,97 @@ fn process_bucket<H>(
 
     rest.into_par_iter()
         .for_each_with(sink.clone(), |sink, candidate| {
-            let hash = full_hash::<H>(&candidate).unwrap_or(old_hash);
-            sink.send(hash, candidate.path.into());
+            let hash = full_hash::<H>(fs, &candidate, full).unwrap_or(old_hash);
+            sink.send(hash, candidate.path);
         });
 
     if large.is_empty() {
         return;
     }
-    // A differing tail hash is proof enough that two files differ, no
-    // full read needed; only files still colliding on both ends pay for
-    // one. Sound because hash *inequality* is exact -- no assumption is
-    // being made, unlike the eventual duplicate verdict which (like the
-    // rest of yadf) trusts hash equality.
-    let by_suffix: TreeBag<H::Hash, Candidate> = large
+    split_by_suffix::<H>(fs, index, old_hash, large)
+        .into_inner()
         .into_par_iter()
-        .map(|candidate| {
-            let hash = suffix_hash::<H>(&candidate).unwrap_or(old_hash);
+        .for_each_with(sink.clone(), |sink, group| {
+            hash_group::<H>(fs, sink, group, full)
+        });
+}
+
+/// Regroups `large`, the bucket at `index` of its bag, by the hash of their
+/// last 4 KiB and of that index: split groups sent on alongside other
+/// buckets never merge with theirs, even when their tails match.
+///
+/// A differing tail hash is proof enough that two files differ, no full
+/// read needed; only files still colliding on both ends pay for one. Sound
+/// because hash *inequality* is exact -- no assumption is being made,
+/// unlike the eventual duplicate verdict which (like the rest of yadf)
+/// trusts hash equality.
+fn split_by_suffix<H>(
+    fs: &dyn FileSystem,
+    index: usize,
+    old_hash: H::Hash,
+    large: Vec<Candidate>,
+) -> TreeBag<H::Hash, Candidate>
+where
+    H: crate::hasher::Hasher,
+{
+    let hashes: Vec<_> = if fs.batches_reads() {
+        let files: Vec<_> = large.iter().map(|c| (c.as_ref(), c.size)).collect();
+        hash::suffixes::<H>(fs, &files, index)
+    } else {
+        large
+            .par_iter()
+            .map(|candidate| hash::suffix::<H>(fs, candidate.as_ref(), candidate.size, index))
+            .collect()
+    };
+    large
+        .into_iter()
+        .zip(hashes)
+        .map(|(candidate, hash)| {
+            let hash = hash
+                .map_err(|error| {
+                    log::error!(
+                        "{}, couldn't hash suffix of {:?}, reusing previous hash",
+                        error,
+                        candidate.as_ref()
+                    )
+                })
+                .unwrap_or(old_hash);
             (hash, candidate)
         })
-        .collect::<Vec<_>>()
-        .into_iter()
-        .collect();
-    by_suffix.into_inner().into_par_iter().for_each_with(
-        sink.clone(),
-        |sink, (suffix_hash, group)| {
-            if group.len() == 1 {
-                let candidate = group.into_iter().next().unwrap();
-                sink.send(suffix_hash, candidate.path.into());
-                return;
-            }
-            group
-                .into_par_iter()
-                .for_each_with(sink.clone(), |sink, candidate| {
-                    let hash = full_hash::<H>(&candidate).unwrap_or(suffix_hash);
-                    sink.send(hash, candidate.path.into());
-                });
-        },
-    );
+        .collect()
+}
+
+/// Reads every candidate of a group of more than one in full, and sends it
+/// on under its full hash; a lone candidate keeps the hash it has.
+fn hash_group<H>(
+    fs: &dyn FileSystem,
+    sink: &Sink<H::Hash, crate::Path>,
+    (old_hash, group): (H::Hash, Vec<Candidate>),
+    full: Option<&FullHashes<H::Hash>>,
+) where
+    H: crate::hasher::Hasher,
+{
+    if group.len() == 1 {
+        let candidate = group.into_iter().next().unwrap();
+        sink.send(old_hash, candidate.path);
+        return;
+    }
+    group
+        .into_par_iter()
+        .for_each_with(sink.clone(), |sink, candidate| {
+            let hash = full_hash::<H>(fs, &candidate, full).unwrap_or(old_hash);
+            sink.send(hash, candidate.path);
+        });
 }
 
 /// The candidate's full-content hash, or `None` if there is nothing to be
-/// gained from reading it and the caller should keep the hash it has.
-fn full_hash<H>(candidate: &Candidate) -> Option<H::Hash>
+/// gained from reading it and the caller should keep the hash it has. The
+/// hash is recorded in `full`, if given.
+fn full_hash<H>(
+    fs: &dyn FileSystem,
+    candidate: &Candidate,
+    full: Option<&FullHashes<H::Hash>>,
+) -> Option<H::Hash>
 where
     H: crate::hasher::Hasher,
 {
@@ -250,31 +1094,18 @@ where
         // size: nothing more to distinguish it by.
         return None;
     }
-    hash::full::<H>(&candidate.path)
+    let hash = hash::full::<H>(fs, candidate.as_ref())
         .map_err(|error| {
             log::error!(
                 "{}, couldn't hash {:?}, reusing previous hash",
                 error,
-                candidate.path
-            )
-        })
-        .ok()
-}
-
-/// The candidate's tail hash, or `None` if it couldn't be read -- in which
-/// case the caller keeps the hash it has, and the file simply stays in its
-/// current group.
-fn suffix_hash<H>(candidate: &Candidate) -> Option<H::Hash>
-where
-    H: crate::hasher::Hasher,
-{
-    hash::suffix::<H>(&candidate.path, candidate.size)
-        .map_err(|error| {
-            log::error!(
-                "{}, couldn't hash suffix of {:?}, reusing previous hash",
-                error,
-                candidate.path
+                candidate.as_ref()
             )
         })
-        .ok()
+        .ok()?;
+    if let Some(full) = full {
+        let path = candidate.as_ref().to_path_buf();
+        full.lock().unwrap().push((hash, path));
+    }
+    Some(hash)
 }
@@ -3,8 +3,8 @@
 //! (unsupported filesystem, non-Linux target) are silently ignored and must
 //! never affect correctness.
 //!
-//! Typed access to this module goes through [`super::file::Reader`] and
-//! [`super::prefetch`]; nothing else should reach for it directly.
+//! Typed access to this module goes through [`super::os::Os`]; nothing else
+//! should reach for it directly.
 
 use super::file::Access;
 use crate::units::Bytes;
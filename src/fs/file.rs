@@ -2,12 +2,14 @@
 //!
 //! Callers say how they mean to read a file ([`Access`]) and what they want
 //! out of it (a prefix, a slice at an offset, the whole content); the
-//! platform tricks -- `O_NOATIME`, `posix_fadvise`, positional reads -- and
-//! the short-read/`EINTR` retry loops stay behind this boundary.
+//! platform tricks -- `O_NOATIME`, `posix_fadvise`, positional reads -- stay
+//! behind the [`FileSystem`], and the short-read/`EINTR` retry loops behind
+//! this boundary.
 
-use super::advise;
 use crate::units::Bytes;
-use std::io::{self, Read};
+pub use crate::vfs::Access;
+use crate::vfs::{FileReader, FileSystem};
+use std::io;
 use std::path::Path;
 
 /// Buffer size for the full-file streaming pass. Large enough that a big
@@ -20,27 +22,13 @@ thread_local! {
     static SCRATCH: std::cell::RefCell<Vec<u8>> = std::cell::RefCell::new(vec![0u8; SCRATCH_SIZE]);
 }
 
-/// How the caller intends to read a file. Passed to the kernel as a hint so
-/// it can size its readahead accordingly.
-#[derive(Debug, Clone, Copy)]
-pub enum Access {
-    /// A short read of one region and nothing else: readahead past it would
-    /// just waste bandwidth on a cold cache.
-    Random,
-    /// A front-to-back read of the whole file, which does want readahead.
-    Sequential,
-}
-
-/// A file opened for reading, with atime updates suppressed where the
-/// platform allows it.
-#[derive(Debug)]
-pub struct Reader(std::fs::File);
+/// A file opened for reading through a [`FileSystem`], which decides how:
+/// with atime updates suppressed and readahead hinted for the OS one.
+pub struct Reader<'a>(Box<dyn FileReader + 'a>);
 
-impl Reader {
-    pub fn open(path: &Path, access: Access) -> io::Result<Self> {
-        let file = advise::open_noatime(path)?;
-        advise::advise(&file, access);
-        Ok(Self(file))
+impl<'a> Reader<'a> {
+    pub fn open(fs: &'a dyn FileSystem, path: &Path, access: Access) -> io::Result<Self> {
+        fs.open(path, access).map(Self)
     }
 
     /// Reads up to `buffer.len()` bytes from the start of the file and
@@ -59,10 +47,9 @@ impl Reader {
         Ok(&buffer[..filled])
     }
 
-    /// Fills `buffer` from `offset`, in a single positional read rather than
-    /// a `seek` + `read` pair where the platform allows it.
-    pub fn read_exact_at(&self, buffer: &mut [u8], offset: Bytes) -> io::Result<()> {
-        read_exact_at(&self.0, buffer, offset.get())
+    /// Fills `buffer` from `offset`.
+    pub fn read_exact_at(&mut self, buffer: &mut [u8], offset: Bytes) -> io::Result<()> {
+        self.0.read_exact_at(buffer, offset.get())
     }
 
     /// Streams the whole file through `sink`, chunk by chunk, using a
@@ -81,17 +68,3 @@ impl Reader {
         })
     }
 }
-
-#[cfg(unix)]
-fn read_exact_at(file: &std::fs::File, buffer: &mut [u8], offset: u64) -> io::Result<()> {
-    use std::os::unix::fs::FileExt;
-    file.read_exact_at(buffer, offset)
-}
-
-#[cfg(not(unix))]
-fn read_exact_at(file: &std::fs::File, buffer: &mut [u8], offset: u64) -> io::Result<()> {
-    use std::io::{Seek, SeekFrom};
-    let mut file = file;
-    file.seek(SeekFrom::Start(offset))?;
-    file.read_exact(buffer)
-}
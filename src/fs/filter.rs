@@ -1,5 +1,7 @@
-use std::fs::Metadata;
+use crate::vfs::Metadata;
+use std::fmt;
 use std::path::Path;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct FileFilter {
@@ -7,6 +9,8 @@ pub struct FileFilter {
     max: Option<u64>,
     regex: Option<regex::Regex>,
     glob: Option<globset::GlobMatcher>,
+    files: Option<Predicate>,
+    directories: Option<Predicate>,
     #[cfg(unix)]
     inodes_filter: inode::Filter,
 }
@@ -24,6 +28,8 @@ impl FileFilter {
             max,
             regex,
             glob,
+            files: None,
+            directories: None,
         }
     }
 
@@ -40,24 +46,46 @@ impl FileFilter {
             max,
             regex,
             glob,
+            files: None,
+            directories: None,
             inodes_filter: inode::Filter::new(disable_hard_links_filter),
         }
     }
 
-    pub fn is_match(&self, path: &Path, meta: Metadata) -> bool {
+    /// Adds the user's checks: `files` on the files which pass the others,
+    /// `directories` on the directories the walk could descend into.
+    pub fn with_predicates(self, files: Option<Predicate>, directories: Option<Predicate>) -> Self {
+        Self {
+            files,
+            directories,
+            ..self
+        }
+    }
+
+    /// Whether the walk goes into the directory at `path`.
+    pub fn descends_into(&self, path: &Path, meta: &Metadata) -> bool {
+        is_accepted(&self.directories, path, meta)
+    }
+
+    pub fn is_match(&self, path: &Path, meta: &Metadata) -> bool {
         // Cheap, lock-free predicates first: the inode check below takes a
         // shared lock, so files rejected by size/name never contend for it.
-        let cheap = meta.is_file()
-            && self.min.is_none_or(|m| meta.len() >= m)
-            && self.max.is_none_or(|m| meta.len() <= m)
+        let cheap = meta.is_file
+            && self.min.is_none_or(|m| meta.len >= m)
+            && self.max.is_none_or(|m| meta.len <= m)
             && is_match(&self.regex, path).unwrap_or(true)
             && is_match(&self.glob, path).unwrap_or(true);
         if !cheap {
             return false;
         }
+        // Before the inode check, so a rejected hard link doesn't shadow an
+        // accepted one.
+        if !is_accepted(&self.files, path, meta) {
+            return false;
+        }
         #[cfg(unix)]
         {
-            if !self.inodes_filter.is_unique(&meta) {
+            if !self.inodes_filter.is_unique(meta) {
                 return false;
             }
         }
@@ -65,6 +93,28 @@ impl FileFilter {
     }
 }
 
+/// A check given by the library user, on top of the built-in ones.
+#[derive(Clone)]
+pub struct Predicate(Arc<Check>);
+
+type Check = dyn Fn(&Path, &Metadata) -> bool + Send + Sync;
+
+impl Predicate {
+    pub fn new(predicate: impl Fn(&Path, &Metadata) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(predicate))
+    }
+}
+
+impl fmt::Debug for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Predicate(..)")
+    }
+}
+
+fn is_accepted(predicate: &Option<Predicate>, path: &Path, meta: &Metadata) -> bool {
+    predicate.as_ref().is_none_or(|p| (p.0)(path, meta))
+}
+
 fn is_match<M: Matcher>(opt: &Option<M>, path: &Path) -> Option<bool> {
     opt.as_ref().and_then(|m| m.is_file_name_match(path))
 }
@@ -89,9 +139,8 @@ impl Matcher for globset::GlobMatcher {
 
 #[cfg(unix)]
 mod inode {
+    use crate::vfs::Metadata;
     use std::collections::HashSet;
-    use std::fs::Metadata;
-    use std::os::unix::fs::MetadataExt;
     use std::sync::Mutex;
 
     /// Inode numbers are only unique within a single device; two files on
@@ -133,10 +182,12 @@ mod inode {
             }
         }
 
+        /// Files without an id can't be told from their hard links, and
+        /// are kept.
         pub fn is_unique(&self, meta: &Metadata) -> bool {
-            match self {
-                Self::Disabled => true,
-                Self::Enabled(set) => set.is_unique(meta.dev(), meta.ino()),
+            match (self, meta.id) {
+                (Self::Enabled(set), Some((dev, ino))) => set.is_unique(dev, ino),
+                _ => true,
             }
         }
     }
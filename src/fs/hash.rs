@@ -4,6 +4,7 @@
 
 use super::file::{Access, Reader};
 use crate::units::Bytes;
+use crate::vfs::{BlockRead, FileSystem};
 use std::io;
 use std::path::Path;
 
@@ -28,41 +29,130 @@ where
 ///
 /// `size` is the already-known file size (from the caller's earlier
 /// `stat`), so this never issues its own `fstat`.
-pub fn partial<H>(path: &Path, size: Bytes) -> io::Result<H::Hash>
+pub fn partial<H>(fs: &dyn FileSystem, path: &Path, size: Bytes) -> io::Result<H::Hash>
 where
     H: crate::hasher::Hasher,
 {
-    let mut file = Reader::open(path, Access::Random)?;
+    let mut file = Reader::open(fs, path, Access::Random)?;
     let mut buffer = [0u8; BLOCK_LEN];
     let prefix = file.read_prefix(&mut buffer)?;
-    let mut hasher = H::default();
-    hasher.write(&size.to_le_bytes());
-    hasher.write(prefix);
-    Ok(hasher.finish())
+    Ok(of_prefix::<H>(size, prefix))
 }
 
-/// Get a checksum of the last 4 KiB (at most) of a file. Cheap way to split
-/// apart large files that only share a header before paying for a full read.
-pub fn suffix<H>(path: &Path, size: Bytes) -> io::Result<H::Hash>
+/// Get a checksum of the last 4 KiB (at most) of a file, and of `bucket`,
+/// the index of the group it was in so far. Cheap way to split apart large
+/// files that only share a header before paying for a full read, without
+/// ever grouping files which were apart.
+pub fn suffix<H>(
+    fs: &dyn FileSystem,
+    path: &Path,
+    size: Bytes,
+    bucket: usize,
+) -> io::Result<H::Hash>
 where
     H: crate::hasher::Hasher,
 {
-    let file = Reader::open(path, Access::Random)?;
+    let mut file = Reader::open(fs, path, Access::Random)?;
     let len = size.min(BLOCK);
     let mut buffer = [0u8; BLOCK_LEN];
     let tail = &mut buffer[..len.as_usize()];
     file.read_exact_at(tail, size - len)?;
+    Ok(of_suffix::<H>(bucket, tail))
+}
+
+/// The checksum a group of duplicates holding the file at `path` is keyed
+/// by: [`partial`] for a file under [`BLOCK`], which covers all of it, and
+/// [`full`] otherwise.
+pub fn group_key<H>(fs: &dyn FileSystem, path: &Path) -> io::Result<H::Hash>
+where
+    H: crate::hasher::Hasher,
+{
+    let size = Bytes::new(fs.metadata(path)?.len);
+    if size < BLOCK {
+        partial::<H>(fs, path, size)
+    } else {
+        full::<H>(fs, path)
+    }
+}
+
+/// [`partial`] for each of `files`, given as paths and sizes, read in as
+/// few round trips as [`FileSystem::read_batch`] manages.
+pub fn partials<H>(fs: &dyn FileSystem, files: &[(&Path, Bytes)]) -> Vec<io::Result<H::Hash>>
+where
+    H: crate::hasher::Hasher,
+{
+    let reads = files.iter().map(|&(path, size)| BlockRead {
+        path,
+        offset: 0,
+        len: size.min(BLOCK).as_usize(),
+    });
+    read_batch(fs, reads.collect(), |index, prefix| {
+        of_prefix::<H>(files[index].1, prefix)
+    })
+}
+
+/// [`suffix`] for each of `files`, like [`partials`], all in `bucket` so
+/// far.
+pub fn suffixes<H>(
+    fs: &dyn FileSystem,
+    files: &[(&Path, Bytes)],
+    bucket: usize,
+) -> Vec<io::Result<H::Hash>>
+where
+    H: crate::hasher::Hasher,
+{
+    let reads = files.iter().map(|&(path, size)| {
+        let len = size.min(BLOCK);
+        BlockRead {
+            path,
+            offset: (size - len).get(),
+            len: len.as_usize(),
+        }
+    });
+    read_batch(fs, reads.collect(), |_, tail| of_suffix::<H>(bucket, tail))
+}
+
+fn read_batch<T>(
+    fs: &dyn FileSystem,
+    reads: Vec<BlockRead<'_>>,
+    hash: impl Fn(usize, &[u8]) -> T,
+) -> Vec<io::Result<T>> {
+    let mut hashes: Vec<Option<io::Result<T>>> = reads.iter().map(|_| None).collect();
+    fs.read_batch(&reads, &mut |index, block| {
+        hashes[index] = Some(block.map(|block| hash(index, block)));
+    });
+    hashes
+        .into_iter()
+        .map(|hash| hash.unwrap_or_else(|| Err(io::Error::other("never read"))))
+        .collect()
+}
+
+fn of_prefix<H>(size: Bytes, prefix: &[u8]) -> H::Hash
+where
+    H: crate::hasher::Hasher,
+{
     let mut hasher = H::default();
+    hasher.write(&size.to_le_bytes());
+    hasher.write(prefix);
+    hasher.finish()
+}
+
+fn of_suffix<H>(bucket: usize, tail: &[u8]) -> H::Hash
+where
+    H: crate::hasher::Hasher,
+{
+    let mut hasher = H::default();
+    hasher.write(&(bucket as u64).to_le_bytes());
     hasher.write(tail);
-    Ok(hasher.finish())
+    hasher.finish()
 }
 
 /// Get a complete checksum of a file.
-pub fn full<H>(path: &Path) -> io::Result<H::Hash>
+pub fn full<H>(fs: &dyn FileSystem, path: &Path) -> io::Result<H::Hash>
 where
     H: crate::hasher::Hasher,
 {
-    let mut file = Reader::open(path, Access::Sequential)?;
+    let mut file = Reader::open(fs, path, Access::Sequential)?;
     let mut hasher = H::default();
     file.for_each_chunk(|chunk| hasher.write(chunk))?;
     Ok(hasher.finish())
@@ -76,8 +166,8 @@ mod tests {
     fn different_hash_partial_and_full_for_small_file_because_of_size() {
         let path: &Path = "./tests/static/foo".as_ref();
         let size = Bytes::new(std::fs::metadata(path).unwrap().len());
-        let h1 = partial::<seahash::SeaHasher>(path, size).unwrap();
-        let h2 = full::<seahash::SeaHasher>(path).unwrap();
+        let h1 = partial::<seahash::SeaHasher>(&crate::vfs::Os::default(), path, size).unwrap();
+        let h2 = full::<seahash::SeaHasher>(&crate::vfs::Os::default(), path).unwrap();
         assert_ne!(h1, h2);
     }
 
@@ -87,17 +177,20 @@ mod tests {
         let path = dir.join("suffix-test");
         let size = Bytes::new(8192);
         std::fs::write(&path, [b'a'; 8192]).unwrap();
-        let h_all_a = suffix::<seahash::SeaHasher>(&path, size).unwrap();
+        let h_all_a =
+            suffix::<seahash::SeaHasher>(&crate::vfs::Os::default(), &path, size, 0).unwrap();
         let mut content = vec![b'a'; 8192];
         content[8191] = b'b';
         std::fs::write(&path, &content).unwrap();
-        let h_last_byte_differs = suffix::<seahash::SeaHasher>(&path, size).unwrap();
+        let h_last_byte_differs =
+            suffix::<seahash::SeaHasher>(&crate::vfs::Os::default(), &path, size, 0).unwrap();
         assert_ne!(h_all_a, h_last_byte_differs);
 
         let mut content = vec![b'a'; 8192];
         content[0] = b'b';
         std::fs::write(&path, &content).unwrap();
-        let h_first_byte_differs = suffix::<seahash::SeaHasher>(&path, size).unwrap();
+        let h_first_byte_differs =
+            suffix::<seahash::SeaHasher>(&crate::vfs::Os::default(), &path, size, 0).unwrap();
         assert_eq!(
             h_all_a, h_first_byte_differs,
             "suffix hash must not be affected by a change outside the last 4 KiB"
@@ -105,6 +198,43 @@ mod tests {
         std::fs::remove_dir_all(dir).unwrap();
     }
 
+    #[test]
+    fn batched_hashes_match_single_reads() {
+        let dir = std::env::temp_dir().join(format!("yadf-batch-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut files = Vec::new();
+        for len in [0, 10, 4096, 9000] {
+            let path = dir.join(len.to_string());
+            std::fs::write(&path, (0..len).map(|i| i as u8).collect::<Vec<_>>()).unwrap();
+            files.push((path, Bytes::new(len)));
+        }
+        let missing = dir.join("missing");
+        files.push((missing.clone(), Bytes::new(10)));
+        let files: Vec<_> = files
+            .iter()
+            .map(|(path, size)| (path.as_path(), *size))
+            .collect();
+        let fs = crate::vfs::Os::default().io_uring(true);
+        let partials = partials::<seahash::SeaHasher>(&fs, &files);
+        let suffixes = suffixes::<seahash::SeaHasher>(&fs, &files, 1);
+        for ((&(path, size), partial_hash), suffix_hash) in files.iter().zip(partials).zip(suffixes)
+        {
+            if path == missing {
+                assert!(partial_hash.is_err() && suffix_hash.is_err());
+                continue;
+            }
+            assert_eq!(
+                partial_hash.unwrap(),
+                partial::<seahash::SeaHasher>(&fs, path, size).unwrap()
+            );
+            assert_eq!(
+                suffix_hash.unwrap(),
+                suffix::<seahash::SeaHasher>(&fs, path, size, 1).unwrap()
+            );
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
     fn tempdir() -> std::path::PathBuf {
         let dir = std::env::temp_dir().join(format!("yadf-hash-test-{}", std::process::id()));
         std::fs::create_dir_all(&dir).unwrap();
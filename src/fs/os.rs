@@ -0,0 +1,334 @@
+//! The operating system's filesystem, walked by `ignore`'s parallel walker
+//! and read through the [`advise`] layer.
+
+use super::advise;
+use crate::ext::{IteratorExt, WalkBuilderAddPaths, WalkParallelForEach};
+use crate::units::Bytes;
+use crate::vfs::{Access, BlockRead, Entry, FileReader, FileSystem, Metadata, Visit};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use std::fmt;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// The operating system's filesystem, the default.
+///
+/// Files are opened without updating their access time where the platform
+/// allows it, and on Linux the kernel is told how they are about to be read.
+///
+/// The walk goes through every file, ignoring nothing, on as many threads
+/// as there are CPUs. Any other [`ignore::WalkBuilder`] option can be set
+/// with [`Os::with_walker`]:
+///
+/// ```no_run
+/// # fn foo(paths: &[std::path::PathBuf]) {
+/// use yadf::vfs::Os;
+///
+/// let files = Os::with_walker(|walker| {
+///     walker.git_ignore(true).same_file_system(true);
+/// });
+/// let counter = yadf::Yadf::builder()
+///     .paths(paths)
+///     .filesystem(files)
+///     .build()
+///     .scan::<highway::HighwayHasher>();
+/// # }
+/// ```
+#[derive(Default, Clone)]
+pub struct Os {
+    walker: Option<Arc<Configure>>,
+    io_uring: bool,
+    stat_in_batches: bool,
+}
+
+type Configure = dyn Fn(&mut ignore::WalkBuilder) + Send + Sync;
+
+/// A file opened by [`Os`].
+#[derive(Debug)]
+struct File(std::fs::File);
+
+impl Os {
+    /// The operating system's filesystem, walked by a [`ignore::WalkBuilder`]
+    /// which `configure` adjusts once the defaults and the paths are set.
+    pub fn with_walker(
+        configure: impl Fn(&mut ignore::WalkBuilder) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            walker: Some(Arc::new(configure)),
+            ..Self::default()
+        }
+    }
+
+    /// Whether to read the blocks of the partial and suffix passes through
+    /// io_uring, opening, reading and closing whole batches of files in a
+    /// few system calls rather than one thread and three calls per file.
+    ///
+    /// Only on Linux, with the `io-uring` feature, and if the kernel allows
+    /// it; otherwise files are read the usual way.
+    pub fn io_uring(mut self, enabled: bool) -> Self {
+        self.io_uring = enabled;
+        self
+    }
+
+    /// Whether the walk fetches the metadata of files in batches, rather
+    /// than one `stat` after the other on each walker thread, which is what
+    /// the walk spends its time on where each one waits on the network.
+    ///
+    /// A batch goes through io_uring where [`Os::io_uring`] allows it, and
+    /// is spread over a few threads otherwise. Directories are still looked
+    /// at one by one, as the walk needs to know about one before going into
+    /// it.
+    pub fn stat_in_batches(mut self, enabled: bool) -> Self {
+        self.stat_in_batches = enabled;
+        self
+    }
+
+    /// The metadata of each of `paths`, following links if `follow_links`.
+    fn stat_batch(&self, paths: &[&Path], follow_links: bool) -> Vec<io::Result<Metadata>> {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if self.io_uring && super::uring::can_stat() {
+            let mut stats: Vec<_> = paths.iter().map(|_| None).collect();
+            let batch = super::uring::stat_batch(paths, follow_links, &mut |index, metadata| {
+                stats[index] = Some(metadata);
+            });
+            match batch {
+                Ok(()) => {
+                    return stats
+                        .into_iter()
+                        .map(|stat| stat.unwrap_or_else(|| Err(io::Error::other("never read"))))
+                        .collect()
+                }
+                Err(error) => log::debug!("{}, couldn't set up an io_uring", error),
+            }
+        }
+        let stat = if follow_links {
+            std::fs::metadata
+        } else {
+            std::fs::symlink_metadata
+        };
+        paths
+            .par_iter()
+            .map(|path| stat(path).map(|meta| Metadata::from(&meta)))
+            .collect()
+    }
+}
+
+/// How many files a walker thread gathers before fetching their metadata.
+const STAT_BATCH: usize = 256;
+
+/// Builds the visitor of each walker thread when files' metadata is fetched
+/// in batches.
+#[derive(Clone, Copy)]
+struct Batched<'a> {
+    os: &'a Os,
+    visit: &'a (dyn Fn(io::Result<Entry>) -> Visit + Sync),
+    quit: &'a AtomicBool,
+}
+
+/// A walker thread's visitor, holding on to the files it is given until it
+/// has a batch of them, or until the walk is over.
+struct BatchVisitor<'a> {
+    batched: Batched<'a>,
+    files: Vec<PathBuf>,
+    /// Whether the walker was seen following a link to a file, which it
+    /// only does if [`ignore::WalkBuilder::follow_links`] says so: the
+    /// files are then stat'ed the way it would have.
+    follows_links: bool,
+}
+
+impl<'s> ignore::ParallelVisitorBuilder<'s> for Batched<'s> {
+    fn build(&mut self) -> Box<dyn ignore::ParallelVisitor + 's> {
+        Box::new(BatchVisitor {
+            batched: *self,
+            files: Vec::with_capacity(STAT_BATCH),
+            follows_links: false,
+        })
+    }
+}
+
+impl ignore::ParallelVisitor for BatchVisitor<'_> {
+    fn visit(&mut self, entry: Result<ignore::DirEntry, ignore::Error>) -> ignore::WalkState {
+        match entry {
+            Ok(entry) if entry.file_type().is_some_and(|kind| kind.is_file()) => {
+                self.follows_links |= entry.path_is_symlink();
+                self.files.push(entry.into_path());
+                if self.files.len() < STAT_BATCH {
+                    return ignore::WalkState::Continue;
+                }
+                self.flush()
+            }
+            entry => walk_state((self.batched.visit)(to_entry(entry))),
+        }
+    }
+}
+
+impl BatchVisitor<'_> {
+    /// Visits the files gathered so far, unless the walk was stopped.
+    fn flush(&mut self) -> ignore::WalkState {
+        let files = std::mem::take(&mut self.files);
+        if self.batched.quit.load(Ordering::Relaxed) {
+            return ignore::WalkState::Quit;
+        }
+        let paths: Vec<&Path> = files.iter().map(PathBuf::as_path).collect();
+        let stats = self.batched.os.stat_batch(&paths, self.follows_links);
+        for (path, metadata) in files.into_iter().zip(stats) {
+            let entry = match metadata {
+                Ok(metadata) => Ok(Entry { path, metadata }),
+                Err(error) => Err(metadata_error(error, &path)),
+            };
+            if (self.batched.visit)(entry) == Visit::Quit {
+                self.batched.quit.store(true, Ordering::Relaxed);
+                return ignore::WalkState::Quit;
+            }
+        }
+        ignore::WalkState::Continue
+    }
+}
+
+impl Drop for BatchVisitor<'_> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+/// What [`FileSystem::walk`] makes of an entry of `ignore`'s walker.
+fn to_entry(entry: Result<ignore::DirEntry, ignore::Error>) -> io::Result<Entry> {
+    let entry = entry.map_err(io::Error::other)?;
+    let metadata = entry
+        .metadata()
+        .map_err(|error| metadata_error(error, entry.path()))?;
+    Ok(Entry {
+        metadata: Metadata::from(&metadata),
+        path: entry.into_path(),
+    })
+}
+
+fn metadata_error(error: impl fmt::Display, path: &Path) -> io::Error {
+    io::Error::other(format!("{}, couldn't get metadata for {:?}", error, path))
+}
+
+fn walk_state(visit: Visit) -> ignore::WalkState {
+    match visit {
+        Visit::Continue => ignore::WalkState::Continue,
+        Visit::Skip => ignore::WalkState::Skip,
+        Visit::Quit => ignore::WalkState::Quit,
+    }
+}
+
+impl fmt::Debug for Os {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let walker = self.walker.as_ref().map(|_| "..");
+        f.debug_struct("Os")
+            .field("walker", &walker)
+            .field("io_uring", &self.io_uring)
+            .field("stat_in_batches", &self.stat_in_batches)
+            .finish()
+    }
+}
+
+impl FileSystem for Os {
+    fn walk(
+        &self,
+        roots: &[&Path],
+        max_depth: Option<usize>,
+        visit: &(dyn Fn(io::Result<Entry>) -> Visit + Sync),
+    ) {
+        let mut roots = roots
+            .iter()
+            .unique_by(|path| dunce::canonicalize(path).ok());
+        let Some(first) = roots.next() else {
+            return;
+        };
+        let mut walker = ignore::WalkBuilder::new(first);
+        walker
+            .add_paths(roots)
+            .standard_filters(false)
+            .max_depth(max_depth)
+            .threads(num_cpus::get());
+        if let Some(configure) = &self.walker {
+            configure(&mut walker);
+        }
+        let walker = walker.build_parallel();
+        if self.stat_in_batches {
+            let quit = AtomicBool::new(false);
+            return walker.visit(&mut Batched {
+                os: self,
+                visit,
+                quit: &quit,
+            });
+        }
+        walker.for_each(|entry| walk_state(visit(to_entry(entry))))
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        std::fs::metadata(path).map(|meta| Metadata::from(&meta))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        std::fs::symlink_metadata(path).map(|meta| Metadata::from(&meta))
+    }
+
+    fn open(&self, path: &Path, access: Access) -> io::Result<Box<dyn FileReader + '_>> {
+        let file = advise::open_noatime(path)?;
+        advise::advise(&file, access);
+        Ok(Box::new(File(file)))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        dunce::canonicalize(path)
+    }
+
+    fn prefetches(&self) -> bool {
+        advise::PREFETCH_SUPPORTED
+    }
+
+    fn prefetch(&self, path: &Path, len: u64) {
+        advise::prefetch(path, Bytes::new(len));
+    }
+
+    fn batches_reads(&self) -> bool {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        return self.io_uring && super::uring::can_read();
+        #[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+        return false;
+    }
+
+    /// Through io_uring if [`Os::io_uring`] says so, in a ring of this
+    /// thread's own.
+    fn read_batch(&self, reads: &[BlockRead<'_>], done: &mut dyn FnMut(usize, io::Result<&[u8]>)) {
+        #[cfg(all(target_os = "linux", feature = "io-uring"))]
+        if self.batches_reads() {
+            match super::uring::read_batch(reads, done) {
+                Ok(()) => return,
+                Err(error) => log::debug!("{}, couldn't set up an io_uring", error),
+            }
+        }
+        crate::vfs::read_each(self, reads, done);
+    }
+}
+
+impl FileReader for File {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buffer)
+    }
+
+    /// In a single positional read rather than a `seek` + `read` pair where
+    /// the platform allows it.
+    fn read_exact_at(&mut self, buffer: &mut [u8], offset: u64) -> io::Result<()> {
+        read_exact_at(&mut self.0, buffer, offset)
+    }
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &mut std::fs::File, buffer: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(buffer, offset)
+}
+
+#[cfg(not(unix))]
+fn read_exact_at(file: &mut std::fs::File, buffer: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::io::{Seek, SeekFrom};
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(buffer)
+}
@@ -42,15 +42,20 @@ impl<K, V> Clone for Sink<K, V> {
 ///
 /// Returns once `produce` has returned and every clone of its [`Sink`] has
 /// been dropped, which is what closes the channel and ends the collector.
-pub fn collect<K, V>(produce: impl FnOnce(Sink<K, V>) + Send) -> TreeBag<K, V>
+///
+/// The collector gets a thread of its own rather than a `rayon::join` slot:
+/// on a single-threaded pool (one CPU, or `--io-threads 1`) `join` would run
+/// the collector inline and wait forever for a producer nobody is left to
+/// run.
+pub fn collect<K, V>(produce: impl FnOnce(Sink<K, V>)) -> TreeBag<K, V>
 where
     K: Ord + Send,
     V: Send,
 {
     let (sender, receiver) = crossbeam_channel::bounded(CHANNEL_SIZE);
-    rayon::join(
-        move || receiver.into_iter().collect(),
-        move || produce(Sink(sender)),
-    )
-    .0
+    std::thread::scope(|scope| {
+        let collector = scope.spawn(move || receiver.into_iter().collect());
+        produce(Sink(sender));
+        collector.join().expect("collector thread panicked")
+    })
 }
@@ -11,8 +11,8 @@
 //! opens and reads every file itself, so a prefetch that is skipped, fails,
 //! or lands too late costs speed and nothing else.
 
-use super::advise;
 use crate::units::Bytes;
+use crate::vfs::FileSystem;
 use crate::TreeBag;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
@@ -119,16 +119,21 @@ impl Queue {
     /// entries past it. Without that bound it would race to the end of the
     /// queue and fill the page cache with data that gets evicted before
     /// anyone reads it.
-    pub fn warm<T>(&self, window: Window, work: impl FnOnce(&Progress) -> T) -> T {
+    pub fn warm<T>(
+        &self,
+        fs: &dyn FileSystem,
+        window: Window,
+        work: impl FnOnce(&Progress) -> T,
+    ) -> T {
         let progress = Progress::default();
-        if !advise::PREFETCH_SUPPORTED || self.0.is_empty() {
+        if !fs.prefetches() || self.0.is_empty() {
             return work(&progress);
         }
         let cursor = AtomicUsize::new(0);
         let finished = AtomicBool::new(false);
         std::thread::scope(|scope| {
             for _ in 0..THREADS.min(self.0.len()) {
-                scope.spawn(|| self.warm_ahead_of(&progress, window, &cursor, &finished));
+                scope.spawn(|| self.warm_ahead_of(fs, &progress, window, &cursor, &finished));
             }
             let result = work(&progress);
             finished.store(true, Ordering::Release);
@@ -141,6 +146,7 @@ impl Queue {
     /// exhausted or `work` is done.
     fn warm_ahead_of(
         &self,
+        fs: &dyn FileSystem,
         progress: &Progress,
         window: Window,
         cursor: &AtomicUsize,
@@ -160,7 +166,7 @@ impl Queue {
             if finished.load(Ordering::Acquire) {
                 return;
             }
-            advise::prefetch(&request.path, request.len);
+            fs.prefetch(&request.path, request.len.get());
         }
     }
 }
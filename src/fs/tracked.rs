@@ -0,0 +1,111 @@
+//! A [`FileSystem`] noting down every file it failed to read, for the scans
+//! which can't let a file silently drop out of the comparison.
+
+use crate::vfs::{Access, BlockRead, Entry, FileReader, FileSystem, Metadata, Visit};
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Wraps a [`FileSystem`], remembering the files it couldn't open or read.
+#[derive(Debug)]
+pub(super) struct Tracked<'a> {
+    fs: &'a dyn FileSystem,
+    failed: Mutex<HashSet<PathBuf>>,
+}
+
+impl<'a> Tracked<'a> {
+    pub(super) fn new(fs: &'a dyn FileSystem) -> Self {
+        Self {
+            fs,
+            failed: Mutex::default(),
+        }
+    }
+
+    /// Every file which failed to open or to read so far.
+    pub(super) fn into_failed(self) -> HashSet<PathBuf> {
+        self.failed.into_inner().unwrap()
+    }
+
+    fn fail(&self, path: &Path) {
+        self.failed.lock().unwrap().insert(path.to_path_buf());
+    }
+
+    fn track<T>(&self, path: &Path, result: io::Result<T>) -> io::Result<T> {
+        // An interrupted read is tried again by whoever made it.
+        let is_failure = |error: &io::Error| error.kind() != io::ErrorKind::Interrupted;
+        if result.as_ref().is_err_and(is_failure) {
+            self.fail(path);
+        }
+        result
+    }
+}
+
+/// A file opened by [`Tracked`].
+struct File<'a> {
+    tracked: &'a Tracked<'a>,
+    path: PathBuf,
+    inner: Box<dyn FileReader + 'a>,
+}
+
+impl FileSystem for Tracked<'_> {
+    fn walk(
+        &self,
+        roots: &[&Path],
+        max_depth: Option<usize>,
+        visit: &(dyn Fn(io::Result<Entry>) -> Visit + Sync),
+    ) {
+        self.fs.walk(roots, max_depth, visit)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.fs.metadata(path)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.fs.symlink_metadata(path)
+    }
+
+    fn open(&self, path: &Path, access: Access) -> io::Result<Box<dyn FileReader + '_>> {
+        let inner = self.track(path, self.fs.open(path, access))?;
+        Ok(Box::new(File {
+            tracked: self,
+            path: path.to_path_buf(),
+            inner,
+        }))
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.fs.canonicalize(path)
+    }
+
+    fn prefetches(&self) -> bool {
+        self.fs.prefetches()
+    }
+
+    fn prefetch(&self, path: &Path, len: u64) {
+        self.fs.prefetch(path, len)
+    }
+
+    fn batches_reads(&self) -> bool {
+        self.fs.batches_reads()
+    }
+
+    fn read_batch(&self, reads: &[BlockRead<'_>], done: &mut dyn FnMut(usize, io::Result<&[u8]>)) {
+        self.fs.read_batch(reads, &mut |index, result| {
+            done(index, self.track(reads[index].path, result))
+        })
+    }
+}
+
+impl FileReader for File<'_> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        let result = self.inner.read(buffer);
+        self.tracked.track(&self.path, result)
+    }
+
+    fn read_exact_at(&mut self, buffer: &mut [u8], offset: u64) -> io::Result<()> {
+        let result = self.inner.read_exact_at(buffer, offset);
+        self.tracked.track(&self.path, result)
+    }
+}
@@ -0,0 +1,471 @@
+//! Batched file operations through io_uring: the `openat`, `read` and
+//! `close` of many files at once for the partial and suffix passes, and the
+//! `statx` of many files at once for the walk, kept [`DEPTH`] files deep in
+//! a single thread.
+//!
+//! Only [`super::os::Os`] reaches for this, and only once [`can_read`] or
+//! [`can_stat`] has said the kernel can do it; anything else falls back to
+//! the threaded path.
+
+use crate::vfs::{BlockRead, Metadata};
+use io_uring::{opcode, types, IoUring};
+use std::cell::Cell;
+use std::ffi::CString;
+use std::io;
+use std::mem::MaybeUninit;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// How many files a ring has in flight at once, each with a single request
+/// in the queue at any time.
+const DEPTH: u32 = 64;
+
+thread_local! {
+    // Rings are kept from one batch to the next, saving their set up to the
+    // batches of a few files the suffix pass makes.
+    static RING: Cell<Option<IoUring>> = const { Cell::new(None) };
+}
+
+/// Whether io_uring is there, and allowed, and able to open, read and close
+/// files.
+pub fn can_read() -> bool {
+    supports(&[
+        opcode::OpenAt::CODE,
+        opcode::Read::CODE,
+        opcode::Close::CODE,
+    ])
+}
+
+/// Whether io_uring is there, and allowed, and able to `statx` files.
+pub fn can_stat() -> bool {
+    supports(&[opcode::Statx::CODE])
+}
+
+/// Whether the kernel supports all of `codes`, probed once per process.
+fn supports(codes: &[u8]) -> bool {
+    static SUPPORTED: OnceLock<Vec<u8>> = OnceLock::new();
+    let supported = SUPPORTED.get_or_init(|| {
+        let probe = || -> io::Result<Vec<u8>> {
+            let ring = IoUring::new(2)?;
+            let mut probe = io_uring::Probe::new();
+            ring.submitter().register_probe(&mut probe)?;
+            Ok((0..=u8::MAX)
+                .filter(|&code| probe.is_supported(code))
+                .collect())
+        };
+        probe().unwrap_or_else(|error| {
+            log::debug!("{}, io_uring is unavailable", error);
+            Vec::new()
+        })
+    });
+    codes.iter().all(|code| supported.contains(code))
+}
+
+/// This thread's ring, set up if it has none yet. It goes back with
+/// [`RING`]`.set` once nothing is in flight.
+fn ring() -> io::Result<IoUring> {
+    match RING.take() {
+        Some(ring) => Ok(ring),
+        None => IoUring::new(DEPTH),
+    }
+}
+
+/// Where one read stands.
+enum Stage {
+    /// Opening, with `O_NOATIME` until the kernel refuses it.
+    Open {
+        noatime: bool,
+    },
+    Read {
+        fd: i32,
+    },
+    /// Closing, whatever became of the read.
+    Close {
+        fd: i32,
+        result: io::Result<()>,
+    },
+    Done {
+        result: io::Result<()>,
+    },
+}
+
+struct Slot {
+    index: usize,
+    path: CString,
+    offset: u64,
+    buffer: Box<[u8]>,
+    filled: usize,
+    stage: Stage,
+}
+
+impl Slot {
+    /// The request for the slot's current stage. The path and buffer it
+    /// points into stay put until the slot is freed, since they are heap
+    /// allocations the slot owns.
+    fn entry(&mut self, slot: usize) -> io_uring::squeue::Entry {
+        let entry = match &self.stage {
+            Stage::Open { noatime } => {
+                let mut flags = rustix::fs::OFlags::RDONLY | rustix::fs::OFlags::CLOEXEC;
+                if *noatime {
+                    flags |= rustix::fs::OFlags::NOATIME;
+                }
+                let cwd = types::Fd(rustix::fs::CWD.as_raw_fd());
+                opcode::OpenAt::new(cwd, self.path.as_ptr())
+                    .flags(flags.bits() as i32)
+                    .build()
+            }
+            Stage::Read { fd } => {
+                let rest = &mut self.buffer[self.filled..];
+                opcode::Read::new(types::Fd(*fd), rest.as_mut_ptr(), rest.len() as u32)
+                    .offset(self.offset + self.filled as u64)
+                    .build()
+            }
+            Stage::Close { fd, .. } => opcode::Close::new(types::Fd(*fd)).build(),
+            Stage::Done { .. } => unreachable!("a done slot makes no request"),
+        };
+        entry.user_data(slot as u64)
+    }
+
+    /// Closes the file the slot holds open, if any, once nothing more is to
+    /// be asked of the ring.
+    fn close(&self) {
+        if let Stage::Read { fd } | Stage::Close { fd, .. } = self.stage {
+            // SAFETY: the file was opened for the slot, and no request of
+            // its own is left to use or close it.
+            drop(unsafe { OwnedFd::from_raw_fd(fd) });
+        }
+    }
+}
+
+/// Reads every block of `reads` as [`crate::vfs::FileSystem::read_batch`]
+/// says, or fails before the first one if no ring could be set up.
+pub fn read_batch(
+    reads: &[BlockRead<'_>],
+    done: &mut dyn FnMut(usize, io::Result<&[u8]>),
+) -> io::Result<()> {
+    let mut ring = ring()?;
+    let mut slots: Vec<Option<Slot>> = (0..DEPTH).map(|_| None).collect();
+    let mut free: Vec<usize> = (0..slots.len()).rev().collect();
+    let mut next = reads.iter().enumerate();
+    let mut in_flight = 0;
+    loop {
+        while let Some(slot) = free.pop() {
+            let Some((index, read)) = next.next() else {
+                free.push(slot);
+                break;
+            };
+            let path = match CString::new(read.path.as_os_str().as_bytes()) {
+                Ok(path) => path,
+                Err(error) => {
+                    free.push(slot);
+                    done(
+                        index,
+                        Err(io::Error::new(io::ErrorKind::InvalidInput, error)),
+                    );
+                    continue;
+                }
+            };
+            let slot_state = slots[slot].insert(Slot {
+                index,
+                path,
+                offset: read.offset,
+                buffer: vec![0; read.len].into_boxed_slice(),
+                filled: 0,
+                stage: Stage::Open { noatime: true },
+            });
+            push(&mut ring, slot_state.entry(slot));
+            in_flight += 1;
+        }
+        if in_flight == 0 {
+            RING.set(Some(ring));
+            return Ok(());
+        }
+        match ring.submit_and_wait(1) {
+            Ok(_) => {}
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => {
+                log::error!("{}, io_uring submission failed", error);
+                let failed = || Err(io::Error::new(error.kind(), error.to_string()));
+                let submitted = in_flight - ring.submission().len();
+                // The requests the kernel took are waited out: a read which
+                // got to its end is done, any other gives up its file.
+                let drained = drain(&mut ring, submitted, |slot, result| {
+                    let mut state = slots[slot].take().expect("a completion for a busy slot");
+                    if advance(&mut state, result) {
+                        state.close();
+                        done(state.index, failed());
+                    } else if let Stage::Done { result } = state.stage {
+                        done(state.index, result.map(|()| &state.buffer[..]));
+                    }
+                });
+                for slot in slots.into_iter().flatten() {
+                    done(slot.index, failed());
+                    if drained {
+                        // Left in the queue, never to be taken once the
+                        // ring is gone.
+                        slot.close();
+                    } else {
+                        // The kernel may still write into the buffers of the
+                        // reads in flight, so they are leaked rather than
+                        // freed.
+                        std::mem::forget(slot);
+                    }
+                }
+                for (index, _) in next {
+                    done(index, failed());
+                }
+                if !drained {
+                    std::mem::forget(ring);
+                }
+                return Ok(());
+            }
+        }
+        let completions: Vec<_> = ring
+            .completion()
+            .map(|entry| (entry.user_data() as usize, entry.result()))
+            .collect();
+        for (slot, result) in completions {
+            let state = slots[slot].as_mut().expect("a completion for a busy slot");
+            if advance(state, result) {
+                push(&mut ring, state.entry(slot));
+                continue;
+            }
+            let state = slots[slot].take().expect("a completion for a busy slot");
+            let Stage::Done { result } = state.stage else {
+                unreachable!("only a done slot has no request to make")
+            };
+            done(state.index, result.map(|()| &state.buffer[..]));
+            free.push(slot);
+            in_flight -= 1;
+        }
+    }
+}
+
+/// A `statx` in flight.
+struct Stat {
+    index: usize,
+    path: CString,
+    statx: Box<MaybeUninit<rustix::fs::Statx>>,
+}
+
+/// Calls `done` with the metadata of each of `paths`, following links if
+/// `follow_links`, in any order but exactly once each, or fails before the
+/// first one if no ring could be set up.
+pub fn stat_batch(
+    paths: &[&Path],
+    follow_links: bool,
+    done: &mut dyn FnMut(usize, io::Result<Metadata>),
+) -> io::Result<()> {
+    let flags = if follow_links {
+        rustix::fs::AtFlags::empty()
+    } else {
+        rustix::fs::AtFlags::SYMLINK_NOFOLLOW
+    };
+    let mut ring = ring()?;
+    let mut slots: Vec<Option<Stat>> = (0..DEPTH).map(|_| None).collect();
+    let mut free: Vec<usize> = (0..slots.len()).rev().collect();
+    let mut next = paths.iter().enumerate();
+    let mut in_flight = 0;
+    loop {
+        while let Some(slot) = free.pop() {
+            let Some((index, path)) = next.next() else {
+                free.push(slot);
+                break;
+            };
+            let path = match CString::new(path.as_os_str().as_bytes()) {
+                Ok(path) => path,
+                Err(error) => {
+                    free.push(slot);
+                    done(
+                        index,
+                        Err(io::Error::new(io::ErrorKind::InvalidInput, error)),
+                    );
+                    continue;
+                }
+            };
+            let stat = slots[slot].insert(Stat {
+                index,
+                path,
+                statx: Box::new(MaybeUninit::zeroed()),
+            });
+            let cwd = types::Fd(rustix::fs::CWD.as_raw_fd());
+            let entry = opcode::Statx::new(
+                cwd,
+                stat.path.as_ptr(),
+                stat.statx.as_mut_ptr().cast::<types::statx>(),
+            )
+            .flags(flags.bits() as i32)
+            .mask(rustix::fs::StatxFlags::BASIC_STATS.bits())
+            .build()
+            .user_data(slot as u64);
+            push(&mut ring, entry);
+            in_flight += 1;
+        }
+        if in_flight == 0 {
+            RING.set(Some(ring));
+            return Ok(());
+        }
+        match ring.submit_and_wait(1) {
+            Ok(_) => {}
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => {
+                log::error!("{}, io_uring submission failed", error);
+                let failed = || Err(io::Error::new(error.kind(), error.to_string()));
+                let submitted = in_flight - ring.submission().len();
+                let drained = drain(&mut ring, submitted, |slot, result| {
+                    let stat = slots[slot].take().expect("a completion for a busy slot");
+                    done(stat.index, stat.metadata(result));
+                });
+                for stat in slots.into_iter().flatten() {
+                    done(stat.index, failed());
+                    if !drained {
+                        // As for reads, the kernel may still fill the
+                        // buffers of the requests in flight.
+                        std::mem::forget(stat);
+                    }
+                }
+                for (index, _) in next {
+                    done(index, failed());
+                }
+                if !drained {
+                    std::mem::forget(ring);
+                }
+                return Ok(());
+            }
+        }
+        let completions: Vec<_> = ring
+            .completion()
+            .map(|entry| (entry.user_data() as usize, entry.result()))
+            .collect();
+        for (slot, result) in completions {
+            let stat = slots[slot].take().expect("a completion for a busy slot");
+            done(stat.index, stat.metadata(result));
+            free.push(slot);
+            in_flight -= 1;
+        }
+    }
+}
+
+impl Stat {
+    /// What its `statx`, completed with `result`, found.
+    fn metadata(&self, result: i32) -> io::Result<Metadata> {
+        if result < 0 {
+            Err(io::Error::from_raw_os_error(-result))
+        } else {
+            // SAFETY: the kernel filled it in, over zeroes already valid for
+            // every field.
+            Ok(metadata(unsafe { self.statx.assume_init_ref() }))
+        }
+    }
+}
+
+/// Waits for the `submitted` requests in flight once a submission failed,
+/// so that the kernel is done with the memory they point into, and hands
+/// each completion to `finish`. The requests still in the submission queue
+/// are never taken, as long as the ring isn't submitted to again.
+///
+/// Returns whether every request could be waited for.
+fn drain(ring: &mut IoUring, submitted: usize, mut finish: impl FnMut(usize, i32)) -> bool {
+    let mut left = submitted;
+    while left > 0 {
+        // SAFETY: nothing is submitted, and no argument is passed.
+        let waited = unsafe {
+            ring.submitter()
+                .enter::<()>(0, 1, io_uring::EnterFlags::GETEVENTS.bits(), None)
+        };
+        match waited {
+            Ok(_) => {}
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => {
+                log::error!("{}, io_uring requests in flight left behind", error);
+                return false;
+            }
+        }
+        for entry in ring.completion() {
+            finish(entry.user_data() as usize, entry.result());
+            left -= 1;
+        }
+    }
+    true
+}
+
+/// What [`Metadata::from`] makes of the `std::fs::Metadata` of the same file.
+fn metadata(statx: &rustix::fs::Statx) -> Metadata {
+    use rustix::fs::FileType;
+    let kind = FileType::from_raw_mode(statx.stx_mode.into());
+    let mtime = statx.stx_mtime;
+    // Rounded toward the epoch, as `Duration::as_secs` does for a time on
+    // either side of it.
+    let modified = if mtime.tv_sec < 0 && mtime.tv_nsec > 0 {
+        mtime.tv_sec + 1
+    } else {
+        mtime.tv_sec
+    };
+    Metadata {
+        is_file: kind == FileType::RegularFile,
+        is_dir: kind == FileType::Directory,
+        len: statx.stx_size,
+        modified: Some(modified),
+        id: Some((
+            rustix::fs::makedev(statx.stx_dev_major, statx.stx_dev_minor),
+            statx.stx_ino,
+        )),
+        nlink: Some(statx.stx_nlink.into()),
+    }
+}
+
+/// Moves `slot` on after its request completed with `result`, and returns
+/// whether it has another one to make.
+fn advance(slot: &mut Slot, result: i32) -> bool {
+    let errno = -result;
+    let error = || io::Error::from_raw_os_error(errno);
+    let stage = std::mem::replace(&mut slot.stage, Stage::Done { result: Ok(()) });
+    slot.stage = match stage {
+        // `O_NOATIME` is a perk, refused for a file owned by another user.
+        Stage::Open { noatime: true } if errno == rustix::io::Errno::PERM.raw_os_error() => {
+            Stage::Open { noatime: false }
+        }
+        Stage::Open { .. } if result < 0 => Stage::Done {
+            result: Err(error()),
+        },
+        Stage::Open { .. } if slot.buffer.is_empty() => Stage::Close {
+            fd: result,
+            result: Ok(()),
+        },
+        Stage::Open { .. } => Stage::Read { fd: result },
+        Stage::Read { fd }
+            if errno == rustix::io::Errno::INTR.raw_os_error()
+                || errno == rustix::io::Errno::AGAIN.raw_os_error() =>
+        {
+            Stage::Read { fd }
+        }
+        Stage::Read { fd } if result < 0 => Stage::Close {
+            fd,
+            result: Err(error()),
+        },
+        Stage::Read { fd } if result == 0 => Stage::Close {
+            fd,
+            result: Err(io::ErrorKind::UnexpectedEof.into()),
+        },
+        Stage::Read { fd } => {
+            slot.filled += result as usize;
+            if slot.filled < slot.buffer.len() {
+                Stage::Read { fd }
+            } else {
+                Stage::Close { fd, result: Ok(()) }
+            }
+        }
+        // A failed close is of no consequence to a read-only file.
+        Stage::Close { result, .. } | Stage::Done { result } => Stage::Done { result },
+    };
+    !matches!(slot.stage, Stage::Done { .. })
+}
+
+fn push(ring: &mut IoUring, entry: io_uring::squeue::Entry) {
+    // SAFETY: every pointer in `entry` points into a slot which outlives the
+    // request, and a ring of `DEPTH` entries never holds more than one
+    // request per slot, so the queue has room.
+    unsafe { ring.submission().push(&entry) }.expect("one request per slot fits the queue");
+}
@@ -0,0 +1,149 @@
+//! A scan running on a thread of its own, watched and cancelled from
+//! another.
+
+use crate::FileCounter;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+/// Where a scan stands, shared between the scan and its [`ScanHandle`].
+#[derive(Debug, Default)]
+pub(crate) struct Monitor {
+    cancelled: AtomicBool,
+    stopped: AtomicBool,
+    phase: AtomicU8,
+    found: AtomicUsize,
+    done: AtomicUsize,
+    total: AtomicUsize,
+}
+
+/// The steps of a scan, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    /// Walking the paths, grouping files by size.
+    Walking,
+    /// Reading the first 4 KiB of the files sharing a size.
+    Partial,
+    /// Reading the files still sharing a hash in full.
+    Full,
+    /// Done, or cancelled.
+    Finished,
+}
+
+/// A snapshot of a scan's progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub phase: Phase,
+    /// Files found by the walk so far.
+    pub found: usize,
+    /// Files gone through the current hashing phase.
+    pub done: usize,
+    /// Files the current hashing phase goes through, 0 while walking.
+    pub total: usize,
+}
+
+/// A scan started by [`Yadf::spawn`](crate::Yadf::spawn).
+///
+/// Dropping the handle leaves the scan running to its end, unwatched.
+#[derive(Debug)]
+pub struct ScanHandle<K> {
+    monitor: Arc<Monitor>,
+    thread: JoinHandle<Option<FileCounter<K>>>,
+}
+
+impl Monitor {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the scan is to stop where it is, noting down that it did if
+    /// so: a scan cancelled too late to leave anything out is complete all
+    /// the same.
+    pub fn should_stop(&self) -> bool {
+        let cancelled = self.cancelled.load(Ordering::Relaxed);
+        if cancelled {
+            self.stopped.store(true, Ordering::Relaxed);
+        }
+        cancelled
+    }
+
+    /// Whether the scan left anything out because it was cancelled.
+    pub fn stopped_early(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+
+    /// Counts one more file found by the walk.
+    pub fn found(&self) {
+        self.found.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Enters `phase`, which goes through `total` files.
+    pub fn start(&self, phase: Phase, total: usize) {
+        self.done.store(0, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+        self.phase.store(phase as u8, Ordering::Relaxed);
+    }
+
+    /// Counts `files` more gone through the current phase.
+    pub fn advance(&self, files: usize) {
+        self.done.fetch_add(files, Ordering::Relaxed);
+    }
+
+    pub fn progress(&self) -> Progress {
+        let phase = match self.phase.load(Ordering::Relaxed) {
+            0 => Phase::Walking,
+            1 => Phase::Partial,
+            2 => Phase::Full,
+            _ => Phase::Finished,
+        };
+        Progress {
+            phase,
+            found: self.found.load(Ordering::Relaxed),
+            done: self.done.load(Ordering::Relaxed),
+            total: self.total.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<K: Send + 'static> ScanHandle<K> {
+    pub(crate) fn spawn<F>(monitor: Arc<Monitor>, scan: F) -> Self
+    where
+        F: FnOnce() -> Option<FileCounter<K>> + Send + 'static,
+    {
+        let thread = std::thread::Builder::new()
+            .name("yadf-scan".into())
+            .spawn(scan)
+            .expect("failed to spawn the scan thread");
+        Self { monitor, thread }
+    }
+}
+
+impl<K> ScanHandle<K> {
+    /// Where the scan stands.
+    pub fn progress(&self) -> Progress {
+        self.monitor.progress()
+    }
+
+    /// Asks the scan to stop as soon as it can: files being read are read to
+    /// the end, no new one is started.
+    pub fn cancel(&self) {
+        self.monitor.cancel();
+    }
+
+    /// Whether the scan has returned, finished or cancelled.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Waits for the scan to return, with its result, or `None` if it was
+    /// cancelled before it was done.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the scan did.
+    pub fn join(self) -> Option<FileCounter<K>> {
+        self.thread
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+}
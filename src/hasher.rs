@@ -8,7 +8,65 @@ pub trait Hash: PartialEq + Eq + PartialOrd + Ord + Send + Sync + Copy {}
 
 impl<T> Hash for T where T: PartialEq + Eq + PartialOrd + Ord + Send + Sync + Copy {}
 
-#[cfg(feature = "build-bin")]
+/// A [`Hash`] which can be told as bytes, to compare it with hashes computed
+/// elsewhere or to write it out. Only the scans which do either need it.
+pub trait HashBytes: Hash {
+    /// The digest as a checksum tool would print it, most significant byte
+    /// first, so it can be compared with hashes computed elsewhere.
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Wraps the hash for hex display and serialization.
+    fn hex(&self) -> Hex<Self> {
+        Hex(*self)
+    }
+}
+
+/// Displays and serializes a [`HashBytes`] in lowercase hex, the way checksum
+/// tools print digests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Hex<H>(pub H);
+
+impl<H: HashBytes> std::fmt::Display for Hex<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0
+            .to_bytes()
+            .iter()
+            .try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+impl<H: HashBytes> serde::Serialize for Hex<H> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+macro_rules! hash_of_integers {
+    ($($ty:ty => |$hash:ident| $words:expr),* $(,)?) => {$(
+        impl HashBytes for $ty {
+            fn to_bytes(&self) -> Vec<u8> {
+                let $hash = self;
+                $words.into_iter().flat_map(|word| word.to_be_bytes()).collect()
+            }
+        }
+    )*};
+}
+
+hash_of_integers! {
+    u32 => |hash| [*hash],
+    u64 => |hash| [*hash],
+    u128 => |hash| [*hash],
+    (u64, u64) => |hash| [hash.0, hash.1],
+    [u64; 4] => |hash| *hash,
+    [u8; 16] => |hash| *hash,
+    [u8; 20] => |hash| *hash,
+    [u8; 32] => |hash| *hash,
+}
+
+#[cfg(feature = "algorithms")]
 impl Hasher for ahash::AHasher {
     type Hash = u64;
     fn write(&mut self, buf: &[u8]) {
@@ -19,7 +77,7 @@ impl Hasher for ahash::AHasher {
     }
 }
 
-#[cfg(feature = "build-bin")]
+#[cfg(feature = "algorithms")]
 impl Hasher for highway::HighwayHasher {
     type Hash = [u64; 4];
     fn write(&mut self, buf: &[u8]) {
@@ -33,7 +91,7 @@ impl Hasher for highway::HighwayHasher {
     }
 }
 
-#[cfg(feature = "build-bin")]
+#[cfg(feature = "algorithms")]
 impl Hasher for metrohash::MetroHash128 {
     type Hash = (u64, u64);
     fn write(&mut self, buf: &[u8]) {
@@ -45,7 +103,7 @@ impl Hasher for metrohash::MetroHash128 {
     }
 }
 
-#[cfg(feature = "build-bin")]
+#[cfg(feature = "algorithms")]
 impl Hasher for seahash::SeaHasher {
     type Hash = u64;
     fn write(&mut self, buf: &[u8]) {
@@ -56,7 +114,7 @@ impl Hasher for seahash::SeaHasher {
     }
 }
 
-#[cfg(feature = "build-bin")]
+#[cfg(feature = "algorithms")]
 impl Hasher for twox_hash::xxhash3_128::Hasher {
     type Hash = u128;
     fn write(&mut self, buf: &[u8]) {
@@ -68,7 +126,7 @@ impl Hasher for twox_hash::xxhash3_128::Hasher {
     }
 }
 
-#[cfg(feature = "build-bin")]
+#[cfg(feature = "algorithms")]
 impl Hasher for blake3::Hasher {
     type Hash = [u8; 32];
     fn write(&mut self, buf: &[u8]) {
@@ -78,3 +136,55 @@ impl Hasher for blake3::Hasher {
         self.finalize().into()
     }
 }
+
+// The cryptographic hashers below are slower than the ones above, and no
+// safer for finding duplicates. They are here for their output: hashes that
+// can be checked against `sha256sum`, `sha1sum` and `md5sum` manifests.
+
+#[cfg(feature = "algorithms")]
+impl Hasher for sha2::Sha256 {
+    type Hash = [u8; 32];
+    fn write(&mut self, buf: &[u8]) {
+        sha2::Digest::update(self, buf);
+    }
+    fn finish(self) -> Self::Hash {
+        sha2::Digest::finalize(self).into()
+    }
+}
+
+#[cfg(feature = "algorithms")]
+impl Hasher for sha1::Sha1 {
+    type Hash = [u8; 20];
+    fn write(&mut self, buf: &[u8]) {
+        sha1::Digest::update(self, buf);
+    }
+    fn finish(self) -> Self::Hash {
+        sha1::Digest::finalize(self).into()
+    }
+}
+
+#[cfg(feature = "algorithms")]
+impl Hasher for md5::Md5 {
+    type Hash = [u8; 16];
+    fn write(&mut self, buf: &[u8]) {
+        md5::Digest::update(self, buf);
+    }
+    fn finish(self) -> Self::Hash {
+        md5::Digest::finalize(self).into()
+    }
+}
+
+/// The checksum object stores report (as in GCS's `crc32c`), rather than a
+/// hash to trust a duplicate verdict to: 32 bits leave a real chance of a
+/// collision between two files of the same size on a large tree.
+#[cfg(feature = "algorithms")]
+impl Hasher for crc32c::Crc32cHasher {
+    type Hash = u32;
+    fn write(&mut self, buf: &[u8]) {
+        std::hash::Hasher::write(self, buf);
+    }
+    fn finish(self) -> Self::Hash {
+        // The `std::hash::Hasher` interface widens the CRC to 64 bits.
+        std::hash::Hasher::finish(&self) as u32
+    }
+}
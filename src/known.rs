@@ -0,0 +1,135 @@
+//! Content hashes known ahead of a scan, loaded from a checksum list.
+//!
+//! The list can be the output of `sha256sum`, `b3sum` and the like, or a
+//! bare list of hex digests. Only the digests matter: the file names in it
+//! may point anywhere, or nowhere any more.
+
+use crate::hasher::HashBytes;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::{self, BufRead};
+
+/// A set of content hashes to look for, or to look past.
+///
+/// They are only comparable with hashes computed by the same algorithm, and
+/// that algorithm must give the same hash for the same content on every run.
+#[derive(Default)]
+pub struct KnownHashes(HashSet<Vec<u8>>);
+
+impl KnownHashes {
+    /// Reads one digest per line, in any of these shapes:
+    ///
+    /// ```text
+    /// 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08  path/to/file
+    /// 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08 *path/to/file
+    /// SHA256 (path/to/file) = 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+    /// 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+    /// ```
+    ///
+    /// Blank lines and lines starting with `#` are skipped.
+    pub fn read<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut known = Self::default();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let digest = match line.rsplit_once(" = ") {
+                // BSD-style tagged line.
+                Some((_, digest)) => digest,
+                // `sha256sum` escapes odd file names by prefixing the line.
+                None => line.trim_start_matches('\\'),
+            };
+            let digest = digest.split_whitespace().next().unwrap_or_default();
+            let bytes = decode_hex(digest).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {:?} is not a hex digest", number + 1, digest),
+                )
+            })?;
+            known.0.insert(bytes);
+        }
+        Ok(known)
+    }
+
+    /// Whether `hash` is in the set.
+    pub fn contains<K: HashBytes>(&self, hash: &K) -> bool {
+        self.0.contains(&hash.to_bytes())
+    }
+
+    /// Whether any digest in the set is `len` bytes long. A set holding none
+    /// can't match a single hash of that length, which usually means the
+    /// list was made with another algorithm.
+    pub fn has_digests_of_len(&self, len: usize) -> bool {
+        self.0.iter().any(|digest| digest.len() == len)
+    }
+
+    /// Returns the number of digests in the set.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if the set holds no digest.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Summarised: a set can hold millions of digests, and [`crate::Yadf`] is
+/// logged whole at debug level.
+impl fmt::Debug for KnownHashes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("KnownHashes").field(&self.0.len()).finish()
+    }
+}
+
+impl<K: HashBytes> FromIterator<K> for KnownHashes {
+    fn from_iter<I>(hashes: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+    {
+        Self(hashes.into_iter().map(|hash| hash.to_bytes()).collect())
+    }
+}
+
+pub(crate) fn decode_hex(digest: &str) -> Option<Vec<u8>> {
+    if digest.is_empty() || !digest.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..digest.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(digest.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_every_line_shape() {
+        let list = "\
+            # a comment\n\
+            \n\
+            00000000000000ff  some/file\n\
+            00000000000000fe *binary/file\n\
+            \\00000000000000fd  odd\\nname\n\
+            SEAHASH (tagged) = 00000000000000fc\n\
+            00000000000000fb\n\
+        ";
+        let known = KnownHashes::read(list.as_bytes()).unwrap();
+        assert_eq!(known.len(), 5);
+        for hash in 0xfb..=0xffu64 {
+            assert!(known.contains(&hash), "{hash:x}");
+        }
+        assert!(!known.contains(&0xfau64));
+    }
+
+    #[test]
+    fn rejects_what_is_not_a_digest() {
+        let error = KnownHashes::read("abc  file\n".as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(KnownHashes::read("zz  file\n".as_bytes()).is_err());
+    }
+}
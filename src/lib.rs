@@ -7,6 +7,12 @@
 //! yadf = { version = "1.4.0", default-features = false }
 //! ```
 //!
+//! The `algorithms` feature brings the hashers the binary ships with, for
+//! [`Yadf::scan_with`] and an [`Algorithm`] picked at runtime.
+//!
+//! Files come from the operating system's filesystem unless another
+//! [`vfs::FileSystem`] is given to [`YadfBuilder::filesystem`].
+//!
 //! A collection of functions and structs to find duplicate files.
 //!
 //! # Example :
@@ -25,20 +31,36 @@
 #![deny(unsafe_code)]
 #![warn(rust_2018_idioms)]
 
+#[cfg(feature = "algorithms")]
+mod algorithm;
 mod bag;
+mod directory;
 mod ext;
 mod fs;
+mod handle;
 mod hasher;
+mod known;
+mod manifest;
 mod path;
 mod units;
+pub mod vfs;
 
+#[cfg(feature = "algorithms")]
+pub use algorithm::{Algorithm, ContentHash, WithHasher};
 pub use bag::{Factor, Fdupes, Machine, TreeBag};
+pub use directory::{group as group_directories, overlaps as directory_overlaps};
+pub use directory::{DirCounter, Overlap, Relation};
 pub use fs::pool::default_threads as default_io_threads;
+pub use fs::{Candidate, Missing};
 pub use globset;
-pub use hasher::Hasher;
-pub use path::Path;
+pub use handle::{Phase, Progress, ScanHandle};
+pub use hasher::{Hash, HashBytes, Hasher, Hex};
+pub use ignore;
+pub use known::KnownHashes;
+pub use manifest::{Change, FileStatus, Manifest};
+pub use path::{Detailed, FileInfo, Path};
 pub use regex;
-use std::rc::Rc;
+use std::sync::Arc;
 
 pub type FileCounter<H> = TreeBag<H, Path>;
 pub type FileReplicates<'a, H> = bag::Replicates<'a, H, Path>;
@@ -61,17 +83,44 @@ pub type FileReplicates<'a, H> = bag::Replicates<'a, H, Path>;
 /// ```
 ///
 /// see the docs for the [`YadfBuilder`]
-#[derive(Debug, typed_builder::TypedBuilder)]
+#[derive(Debug, Clone, typed_builder::TypedBuilder)]
 #[builder(doc)]
 pub struct Yadf<P: AsRef<std::path::Path>> {
     #[builder(setter(into, doc = "Paths that will be checked for duplicate files"))]
-    paths: Rc<[P]>,
+    paths: Arc<[P]>,
     #[builder(default, setter(into, doc = "Minimum file size"))]
     minimum_file_size: Option<u64>,
     #[builder(default, setter(into, doc = "Maximum file size"))]
     maximum_file_size: Option<u64>,
     #[builder(default, setter(into, doc = "Maximum recursion depth"))]
     max_depth: Option<usize>,
+    #[builder(
+        default,
+        setter(doc = "Take the paths as the exact files to scan, without walking anything")
+    )]
+    exact_files: bool,
+    #[builder(
+        default,
+        setter(
+            transform = |predicate: impl Fn(&std::path::Path, &vfs::Metadata) -> bool
+                + Send
+                + Sync
+                + 'static| Some(fs::filter::Predicate::new(predicate)),
+            doc = "Only scan the files this accepts, among those passing the other filters"
+        )
+    )]
+    file_predicate: Option<fs::filter::Predicate>,
+    #[builder(
+        default,
+        setter(
+            transform = |predicate: impl Fn(&std::path::Path, &vfs::Metadata) -> bool
+                + Send
+                + Sync
+                + 'static| Some(fs::filter::Predicate::new(predicate)),
+            doc = "Only descend into the directories this accepts, the paths included"
+        )
+    )]
+    directory_predicate: Option<fs::filter::Predicate>,
     #[builder(default, setter(into, doc = "File name must match this regex"))]
     regex: Option<regex::Regex>,
     #[builder(default, setter(into, doc = "File name must match this glob"))]
@@ -79,6 +128,21 @@ pub struct Yadf<P: AsRef<std::path::Path>> {
     #[cfg(unix)]
     #[builder(default, setter(doc = "Treat hard links as duplicates"))]
     hard_links: bool,
+    #[builder(
+        default,
+        setter(doc = "Keep each file's metadata from the walk, see [`Path::info`]")
+    )]
+    file_info: bool,
+    #[builder(
+        default = Arc::new(vfs::Os::default()),
+        setter(
+            transform = |filesystem: impl vfs::FileSystem + 'static| {
+                Arc::new(filesystem) as Arc<dyn vfs::FileSystem>
+            },
+            doc = "Where to find the files (default: the operating system's filesystem, see [`vfs::Os`])"
+        )
+    )]
+    filesystem: Arc<dyn vfs::FileSystem>,
     #[builder(
         default = fs::pool::default_threads(),
         setter(doc = "Concurrency for the I/O-bound hashing phases (default: number of CPUs)")
@@ -96,26 +160,190 @@ where
         H: hasher::Hasher,
         H::Hash: std::fmt::Debug,
     {
-        #[cfg(unix)]
-        let file_filter = fs::filter::FileFilter::new(
-            self.minimum_file_size,
-            self.maximum_file_size,
-            self.regex,
-            self.glob.map(|g| g.compile_matcher()),
-            self.hard_links,
+        self.scan_recording::<H>(None, &handle::Monitor::default())
+    }
+
+    /// Starts a scan like [`Yadf::scan`] on a thread of its own, and returns
+    /// at once.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # fn foo(paths: Vec<std::path::PathBuf>) {
+    /// let scan = yadf::Yadf::builder()
+    ///     .paths(paths)
+    ///     .build()
+    ///     .spawn::<highway::HighwayHasher>();
+    /// while !scan.is_finished() {
+    ///     let progress = scan.progress();
+    ///     println!("{:?}: {}/{}", progress.phase, progress.done, progress.total);
+    ///     std::thread::sleep(std::time::Duration::from_millis(100));
+    /// }
+    /// let counter = scan.join().expect("nobody cancelled it");
+    /// # }
+    /// ```
+    pub fn spawn<H>(self) -> ScanHandle<H::Hash>
+    where
+        H: hasher::Hasher,
+        H::Hash: std::fmt::Debug + 'static,
+        P: Send + Sync + 'static,
+    {
+        let monitor = Arc::new(handle::Monitor::default());
+        let shared = Arc::clone(&monitor);
+        ScanHandle::spawn(monitor, move || {
+            let bag = self.scan_recording::<H>(None, &shared);
+            shared.start(Phase::Finished, 0);
+            (!shared.stopped_early()).then_some(bag)
+        })
+    }
+
+    /// Scans like [`Yadf::scan`], and lists every file that was read in full
+    /// along with its hash.
+    ///
+    /// Only files that could be duplicates are read in full, and not even
+    /// all of those: files under 4 KiB and files told apart by their first
+    /// or last 4 KiB aren't listed.
+    pub fn scan_with_manifest<H>(self) -> (FileCounter<H::Hash>, Manifest)
+    where
+        H: hasher::Hasher,
+        H::Hash: std::fmt::Debug + HashBytes,
+    {
+        let full = fs::FullHashes::default();
+        let bag = self.scan_recording::<H>(Some(&full), &handle::Monitor::default());
+        let manifest = full.into_inner().unwrap().into_iter().collect();
+        (bag, manifest)
+    }
+
+    /// Walks the configured paths and groups the files passing the filters
+    /// by size, the first stage of [`Yadf::scan`].
+    ///
+    /// The stages can be run one by one, with the bags in between inspected,
+    /// pruned or stored, or stages of one's own inserted:
+    ///
+    /// ```no_run
+    /// # fn foo(paths: &[std::path::PathBuf]) {
+    /// type H = highway::HighwayHasher;
+    /// let config = yadf::Yadf::builder().paths(paths).build();
+    /// let mut by_size = config.walk();
+    /// // Only large files are worth the trouble.
+    /// by_size.as_inner_mut().retain(|&size, _| size >= 1 << 20);
+    /// let by_prefix = config.hash_partial::<H>(by_size);
+    /// let by_suffix = config.hash_suffix::<H>(by_prefix);
+    /// let counter = config.hash_full::<H>(by_suffix);
+    /// # }
+    /// ```
+    pub fn walk(&self) -> TreeBag<u64, Path> {
+        let by_size = fs::collect_by_size(
+            &*self.filesystem,
+            &self.paths,
+            self.depth(),
+            &self.file_filter(),
+            self.file_info,
+            &handle::Monitor::default(),
         );
-        #[cfg(not(unix))]
-        let file_filter = fs::filter::FileFilter::new(
-            self.minimum_file_size,
-            self.maximum_file_size,
-            self.regex,
-            self.glob.map(|g| g.compile_matcher()),
+        let by_size: std::collections::BTreeMap<_, _> = by_size
+            .into_inner()
+            .into_iter()
+            .map(|(size, bucket)| (size.get(), bucket))
+            .collect();
+        by_size.into()
+    }
+
+    /// Groups the files sharing a size by the hash of their first 4 KiB and
+    /// of their size, the second stage of [`Yadf::scan`], see
+    /// [`Yadf::walk`]. A file alone of its size is never read.
+    pub fn hash_partial<H>(&self, by_size: TreeBag<u64, Path>) -> TreeBag<H::Hash, Candidate>
+    where
+        H: hasher::Hasher,
+    {
+        let by_size = by_size
+            .into_inner()
+            .into_iter()
+            .map(|(size, bucket)| (units::Bytes::new(size), bucket));
+        fs::hash_by_size::<H>(
+            &*self.filesystem,
+            TreeBag::from(by_size.collect::<std::collections::BTreeMap<_, _>>()),
+            self.io_threads,
+            &handle::Monitor::default(),
+        )
+    }
+
+    /// Splits the groups of files of at least 64 KiB by the hash of their
+    /// last 4 KiB, the third stage of [`Yadf::scan`], see [`Yadf::walk`].
+    /// The files of different groups stay apart, whatever their tails.
+    pub fn hash_suffix<H>(&self, tree: TreeBag<H::Hash, Candidate>) -> TreeBag<H::Hash, Candidate>
+    where
+        H: hasher::Hasher,
+    {
+        fs::suffix_pass::<H>(&*self.filesystem, tree, self.io_threads)
+    }
+
+    /// Hashes the files of every group of more than one in full, the last
+    /// stage of [`Yadf::scan`], see [`Yadf::walk`]. Files under 4 KiB keep
+    /// their hash, which already covers their whole content.
+    pub fn hash_full<H>(&self, tree: TreeBag<H::Hash, Candidate>) -> FileCounter<H::Hash>
+    where
+        H: hasher::Hasher,
+    {
+        fs::full_pass::<H>(&*self.filesystem, tree, self.io_threads)
+    }
+
+    /// Leaves out the duplicate groups of `bag`, as [`Yadf::scan`] returns
+    /// it, whose content is in `known`.
+    ///
+    /// `H` must be the algorithm the set was made with. Groups of files
+    /// under 4 KiB are keyed by a hash that also covers their size, so one
+    /// file of each is hashed again.
+    pub fn drop_known<H>(
+        &self,
+        bag: FileCounter<H::Hash>,
+        known: &KnownHashes,
+    ) -> FileCounter<H::Hash>
+    where
+        H: hasher::Hasher,
+        H::Hash: HashBytes,
+    {
+        warn_if_incomparable::<H>(known);
+        let bag = fs::drop_known::<H>(&*self.filesystem, bag, known, self.io_threads);
+        log::info!(
+            "{} duplicate groups left once the known ones are dropped",
+            bag.duplicates().iter().count(),
         );
+        bag
+    }
+
+    /// Keys every file of `bag`, as [`Yadf::scan`] returns it, by a hash of
+    /// its whole content, the files without a duplicate included: those are
+    /// only told apart by their size or a partial hash otherwise.
+    ///
+    /// Files of two scans can then be matched even when each holds a single
+    /// copy of a content, as long as both used the same `H`. Files under 4
+    /// KiB are keyed by a hash that also covers their size, like their groups
+    /// are.
+    pub fn hash_singletons<H>(&self, bag: FileCounter<H::Hash>) -> FileCounter<H::Hash>
+    where
+        H: hasher::Hasher,
+    {
+        fs::hash_singletons::<H>(&*self.filesystem, bag, self.io_threads)
+    }
+
+    fn scan_recording<H>(
+        self,
+        full: Option<&fs::FullHashes<H::Hash>>,
+        monitor: &handle::Monitor,
+    ) -> FileCounter<H::Hash>
+    where
+        H: hasher::Hasher,
+        H::Hash: std::fmt::Debug,
+    {
         let bag = fs::find_dupes_partial::<H, _>(
+            &*self.filesystem,
             &self.paths,
-            self.max_depth,
-            file_filter,
+            self.depth(),
+            self.file_filter(),
+            self.file_info,
             self.io_threads,
+            monitor,
         );
         if log::log_enabled!(log::Level::Info) {
             log::info!(
@@ -128,7 +356,7 @@ where
             );
             log::trace!("{:?}", bag);
         }
-        let bag = fs::dedupe::<H>(bag, self.io_threads);
+        let bag = fs::dedupe::<H>(&*self.filesystem, bag, full, self.io_threads, monitor);
         if log::log_enabled!(log::Level::Info) {
             log::info!(
                 "found {} duplicates in {} groups after checksumming",
@@ -139,4 +367,236 @@ where
         }
         bag
     }
+
+    /// Hashes each of the configured paths, taken as a file, with the hash a
+    /// group of duplicates holding it is keyed by, so a group of an earlier
+    /// scan can be checked file by file. Nothing is walked, and no file is
+    /// compared with another.
+    pub fn group_keys<H>(self) -> FileCounter<H::Hash>
+    where
+        H: hasher::Hasher,
+        P: Sync,
+    {
+        fs::group_keys::<H, _>(&*self.filesystem, &self.paths, self.io_threads)
+    }
+
+    /// Looks for every file under the configured paths whose content is in
+    /// `known`, grouped by content.
+    ///
+    /// `H` must be the algorithm the set was made with. Every walked file is
+    /// read in full: a list of hashes gives no sizes to narrow the search by.
+    pub fn find_known<H>(self, known: &KnownHashes) -> FileCounter<H::Hash>
+    where
+        H: hasher::Hasher,
+        H::Hash: HashBytes,
+    {
+        warn_if_incomparable::<H>(known);
+        let found = fs::known_files::<H, _>(
+            &*self.filesystem,
+            &self.paths,
+            self.depth(),
+            self.file_filter(),
+            known,
+            self.io_threads,
+        );
+        log::info!(
+            "found {} files matching {} known hashes",
+            found.as_inner().values().map(Vec::len).sum::<usize>(),
+            found.len(),
+        );
+        found
+    }
+
+    /// Hashes every walked file in full, for a [`Manifest`] of the whole
+    /// tree to compare later scans with, see [`Manifest::diff`].
+    pub fn snapshot<H>(self) -> Manifest
+    where
+        H: hasher::Hasher,
+        H::Hash: HashBytes,
+    {
+        let files = fs::full_hashes::<H, _>(
+            &*self.filesystem,
+            &self.paths,
+            self.depth(),
+            self.file_filter(),
+            |_| true,
+            self.io_threads,
+        );
+        let manifest = Manifest::from_bag(files);
+        log::info!("hashed {} files", manifest.len());
+        manifest
+    }
+
+    /// Like [`Yadf::snapshot`], but every walked file under the hash a group
+    /// of duplicates holding it is keyed by, as a report listing every file
+    /// has them, rather than the hash of its whole content.
+    pub fn group_key_snapshot<H>(self) -> Manifest
+    where
+        H: hasher::Hasher,
+        H::Hash: HashBytes,
+    {
+        let files = fs::walked_group_keys::<H, _>(
+            &*self.filesystem,
+            &self.paths,
+            self.depth(),
+            self.file_filter(),
+            self.io_threads,
+        );
+        let manifest = Manifest::from_bag(files);
+        log::info!("hashed {} files", manifest.len());
+        manifest
+    }
+
+    /// Looks for copies of `files` under the configured paths, returning a
+    /// bag in which every bucket holds one of `files` first, followed by its
+    /// copies.
+    ///
+    /// Only `files` and the walked files sharing a size with one of them are
+    /// read, which makes this far cheaper than a full [`Yadf::scan`]. The
+    /// size and name filters apply to the walked files, not to `files`.
+    pub fn find_copies_of<H, Q>(self, files: &[Q]) -> FileCounter<H::Hash>
+    where
+        H: hasher::Hasher,
+        Q: AsRef<std::path::Path>,
+    {
+        let copies = fs::copies_of::<H, _, _>(
+            &*self.filesystem,
+            files,
+            &self.paths,
+            self.depth(),
+            self.file_filter(),
+            self.io_threads,
+        );
+        log::info!(
+            "found {} copies of {} files",
+            copies
+                .duplicates()
+                .iter()
+                .map(|b| b.len() - 1)
+                .sum::<usize>(),
+            copies.duplicates().iter().count(),
+        );
+        copies
+    }
+
+    /// Walks the configured paths as sources and `destinations` as their
+    /// backups, and returns every source file whose content is found nowhere
+    /// under `destinations`, whatever its name or location there.
+    ///
+    /// A source file sharing its size with no file at the destinations is
+    /// missing without ever being read; the others go through the usual
+    /// hashing passes alongside the destination files of their size.
+    ///
+    /// A source file which couldn't be read is neither: it is returned as
+    /// unverified.
+    ///
+    /// With [`exact_files`](YadfBuilder::exact_files), the configured paths
+    /// are the sources themselves, while `destinations` are still walked.
+    pub fn scan_missing_from<H, Q>(self, destinations: &[Q]) -> Missing
+    where
+        H: hasher::Hasher,
+        Q: AsRef<std::path::Path>,
+    {
+        let missing = fs::missing_from::<H, _, _>(
+            &*self.filesystem,
+            &self.paths,
+            destinations,
+            self.depth(),
+            fs::Depth::Below(self.max_depth),
+            || self.file_filter(),
+            self.io_threads,
+        );
+        log::info!(
+            "found {} files missing from the destinations, {} unverified",
+            missing.missing.len(),
+            missing.unverified.len()
+        );
+        missing
+    }
+
+    /// Scans like [`Yadf::scan`], then groups whole directories by their
+    /// contents instead of reporting individual files.
+    ///
+    /// see [`group_directories`]
+    pub fn scan_directories<H>(self) -> DirCounter
+    where
+        H: hasher::Hasher,
+        H::Hash: std::fmt::Debug,
+    {
+        let paths = Arc::clone(&self.paths);
+        let files = self.scan::<H>();
+        let directories = directory::group(&files, &paths);
+        log::info!(
+            "found {} duplicate directories in {} groups",
+            directories.duplicates().iter().map(Vec::len).sum::<usize>(),
+            directories.duplicates().iter().count(),
+        );
+        directories
+    }
+
+    /// Scans like [`Yadf::scan`], then looks for directories contained in, or
+    /// at least `threshold` similar to, one another.
+    ///
+    /// see [`directory_overlaps`]
+    pub fn scan_overlaps<H>(self, threshold: f64) -> Vec<Overlap>
+    where
+        H: hasher::Hasher,
+        H::Hash: std::fmt::Debug,
+    {
+        let paths = Arc::clone(&self.paths);
+        let files = self.scan::<H>();
+        let overlaps = directory::overlaps(&files, &paths, threshold);
+        log::info!("found {} overlapping directory pairs", overlaps.len());
+        overlaps
+    }
+
+    fn depth(&self) -> fs::Depth {
+        if self.exact_files {
+            fs::Depth::Exact
+        } else {
+            fs::Depth::Below(self.max_depth)
+        }
+    }
+
+    /// A fresh filter each call: it tracks the inodes it has seen, so every
+    /// walk that must not be affected by another needs its own.
+    fn file_filter(&self) -> fs::filter::FileFilter {
+        #[cfg(unix)]
+        let file_filter = fs::filter::FileFilter::new(
+            self.minimum_file_size,
+            self.maximum_file_size,
+            self.regex.clone(),
+            self.glob.as_ref().map(|g| g.compile_matcher()),
+            self.hard_links,
+        );
+        #[cfg(not(unix))]
+        let file_filter = fs::filter::FileFilter::new(
+            self.minimum_file_size,
+            self.maximum_file_size,
+            self.regex.clone(),
+            self.glob.as_ref().map(|g| g.compile_matcher()),
+        );
+        file_filter.with_predicates(
+            self.file_predicate.clone(),
+            self.directory_predicate.clone(),
+        )
+    }
+}
+
+/// A set without a single digest as long as `H`'s can't match anything,
+/// which is worth a warning rather than a silently empty result.
+fn warn_if_incomparable<H>(known: &KnownHashes)
+where
+    H: hasher::Hasher,
+    H::Hash: HashBytes,
+{
+    let len = H::default().finish().to_bytes().len();
+    if !known.is_empty() && !known.has_digests_of_len(len) {
+        log::warn!(
+            "none of the {} known hashes is {} bytes long like this algorithm's, \
+            were they made with another one?",
+            known.len(),
+            len,
+        );
+    }
 }
@@ -2,114 +2,329 @@
 #![warn(rust_2018_idioms)]
 
 mod args;
+mod check;
+mod merge;
+mod report;
+mod snapshot;
+mod verify;
 
 use anyhow::Context;
-use clap::{Parser, ValueEnum};
+use clap::{Parser, Subcommand, ValueEnum};
 use clap_verbosity_flag::ErrorLevel;
 use std::fs::File;
 use std::io::{self, Write};
 use std::path::PathBuf;
 use std::str::FromStr;
-use yadf::{Fdupes, Machine};
+use yadf::{Algorithm, Fdupes, Machine, WithHasher};
 
 fn main() -> anyhow::Result<()> {
     human_panic::setup_panic!();
     let timer = std::time::Instant::now();
-    let args = Args::init_from_env();
+    let args = Args::init_from_env()?;
     log::debug!("{:?}", args);
-    let config = build_config(&args);
-    log::debug!("{:?}", config);
-    args.algorithm.run(args.clone(), config)?;
+    match &args.command {
+        Some(Command::Verify { report, json }) => verify::run(report, *json)?,
+        Some(Command::Check {
+            manifest,
+            algorithm,
+        }) => check::run(manifest, algorithm)?,
+        Some(Command::Snapshot {
+            paths,
+            algorithm,
+            output,
+        }) => snapshot::snapshot(paths, algorithm, output.as_deref())?,
+        Some(Command::Diff {
+            snapshot,
+            paths,
+            algorithm,
+            save,
+            json,
+        }) => snapshot::diff(snapshot, paths, algorithm, save.as_deref(), *json)?,
+        Some(Command::Merge { reports, json }) => merge::run(reports, *json)?,
+        None => {
+            let config = build_config(&args)?;
+            log::debug!("{:?}", config);
+            run(args.algorithm, args.clone(), config)?;
+        }
+    }
     log::debug!("{:?} elapsed", timer.elapsed());
     Ok(())
 }
 
-fn write_output<H>(args: Args, bag: yadf::TreeBag<H::Hash, yadf::Path>) -> Result<(), anyhow::Error>
+fn write_output<K>(args: Args, bag: yadf::TreeBag<K, yadf::Path>) -> Result<(), anyhow::Error>
 where
-    H: yadf::Hasher,
+    K: yadf::HashBytes,
 {
-    let rfactor = args.rfactor.unwrap_or_default();
+    let rfactor = args.rfactor.clone().unwrap_or_default();
+    let replicates = bag.replicates(rfactor.into());
+    with_output(args.output.as_deref(), |writer| {
+        if args.format.is_rich() {
+            args.format.display_rich(writer, replicates)
+        } else if args.with_hashes {
+            args.format.display_with_hashes(writer, replicates)
+        } else {
+            args.format.display(writer, replicates)
+        }
+    })
+}
+
+fn write_directories(args: Args, bag: yadf::DirCounter) -> Result<(), anyhow::Error> {
+    let rfactor = args.rfactor.clone().unwrap_or_default();
     let replicates = bag.replicates(rfactor.into());
-    match args.output {
+    with_output(args.output.as_deref(), |writer| {
+        args.format.display(writer, replicates)
+    })
+}
+
+fn write_report<K>(
+    args: Args,
+    started: std::time::SystemTime,
+    files: usize,
+    bag: yadf::FileCounter<K>,
+) -> Result<(), anyhow::Error>
+where
+    K: yadf::HashBytes,
+{
+    let rfactor = args.rfactor.clone().unwrap_or_default();
+    let report = report::Report {
+        args: &args,
+        started,
+        finished: std::time::SystemTime::now(),
+        stats: report::Stats::of(files, &bag),
+        replicates: bag.replicates(rfactor.into()),
+    };
+    with_output(args.output.as_deref(), |writer| {
+        let mut writer = io::BufWriter::with_capacity(64 * 1024, writer);
+        serde_json::to_writer(&mut writer, &report)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    })
+}
+
+fn write_overlaps(args: Args, overlaps: Vec<yadf::Overlap>) -> Result<(), anyhow::Error> {
+    with_output(args.output.as_deref(), |writer| {
+        args.format.overlaps(writer, &overlaps)
+    })
+}
+
+fn write_paths(args: Args, paths: Vec<yadf::Path>) -> Result<(), anyhow::Error> {
+    with_output(args.output.as_deref(), |writer| {
+        args.format.paths(writer, &paths)
+    })
+}
+
+/// Hands `write` the output file if one was asked for, stdout otherwise.
+fn with_output<F>(output: Option<&std::path::Path>, write: F) -> Result<(), anyhow::Error>
+where
+    F: FnOnce(Box<dyn Write + '_>) -> anyhow::Result<()>,
+{
+    match output {
         Some(path) => {
             let context = || format!("writing output to the file: {:?}", path.display());
-            let file = File::create(&path).with_context(context)?;
-            args.format.display::<_, H>(file, replicates)
+            let file = File::create(path).with_context(context)?;
+            write(Box::new(file))
         }
-        None => args.format.display::<_, H>(io::stdout().lock(), replicates),
+        None => write(Box::new(io::stdout().lock())),
     }
     .context("writing output")?;
     Ok(())
 }
 
 #[cfg(unix)]
-fn build_config(args: &Args) -> yadf::Yadf<PathBuf> {
-    yadf::Yadf::builder()
+fn build_config(args: &Args) -> anyhow::Result<yadf::Yadf<PathBuf>> {
+    Ok(yadf::Yadf::builder()
         .paths(args.paths.as_ref())
+        .exact_files(args.file_list().is_some())
         .minimum_file_size(args.min())
         .maximum_file_size(args.max())
         .regex(args.regex.clone())
         .glob(args.pattern.clone())
         .max_depth(args.max_depth)
         .hard_links(args.hard_links)
+        .file_info(args.format.is_rich())
         .io_threads(args.io_threads.unwrap_or_else(yadf::default_io_threads))
-        .build()
+        .filesystem(
+            yadf::vfs::Os::default()
+                .io_uring(args.io_uring)
+                .stat_in_batches(args.batch_stat),
+        )
+        .build())
 }
 
 #[cfg(not(unix))]
-fn build_config(args: &Args) -> yadf::Yadf<PathBuf> {
-    yadf::Yadf::builder()
+fn build_config(args: &Args) -> anyhow::Result<yadf::Yadf<PathBuf>> {
+    Ok(yadf::Yadf::builder()
         .paths(args.paths.as_ref())
+        .exact_files(args.file_list().is_some())
         .minimum_file_size(args.min())
         .maximum_file_size(args.max())
         .regex(args.regex.clone())
         .glob(args.pattern.clone())
         .max_depth(args.max_depth)
+        .file_info(args.format.is_rich())
         .io_threads(args.io_threads.unwrap_or_else(yadf::default_io_threads))
-        .build()
+        .filesystem(
+            yadf::vfs::Os::default()
+                .io_uring(args.io_uring)
+                .stat_in_batches(args.batch_stat),
+        )
+        .build())
 }
 
-impl Algorithm {
-    fn run<P>(&self, args: Args, config: yadf::Yadf<P>) -> anyhow::Result<()>
-    where
-        P: AsRef<std::path::Path>,
-    {
-        log::debug!("using {:?} hashing", self);
-        match self {
-            Algorithm::AHash => {
-                write_output::<ahash::AHasher>(args, config.scan::<ahash::AHasher>())?
-            }
-            Algorithm::Blake3 => {
-                write_output::<blake3::Hasher>(args, config.scan::<blake3::Hasher>())?
-            }
-            Algorithm::Highway => write_output::<highway::HighwayHasher>(
-                args,
-                config.scan::<highway::HighwayHasher>(),
-            )?,
-            Algorithm::MetroHash => write_output::<metrohash::MetroHash128>(
-                args,
-                config.scan::<metrohash::MetroHash128>(),
-            )?,
-            Algorithm::SeaHash => {
-                write_output::<seahash::SeaHasher>(args, config.scan::<seahash::SeaHasher>())?
+fn read_known(path: &std::path::Path) -> anyhow::Result<yadf::KnownHashes> {
+    let context = || format!("reading known hashes from the file: {:?}", path.display());
+    let file = File::open(path).with_context(context)?;
+    let known = yadf::KnownHashes::read(io::BufReader::new(file)).with_context(context)?;
+    log::debug!("read {} known hashes from {:?}", known.len(), path);
+    Ok(known)
+}
+
+/// Scans with the hasher `H` and writes whichever report was asked for.
+fn scan<H, P>(mut args: Args, config: yadf::Yadf<P>) -> anyhow::Result<()>
+where
+    H: yadf::Hasher,
+    H::Hash: std::fmt::Debug + yadf::HashBytes,
+    P: AsRef<std::path::Path> + Clone,
+{
+    let started = std::time::SystemTime::now();
+    let is_file_scan = args.known.is_none()
+        && args.find_copies_of.is_empty()
+        && args.missing_from.is_empty()
+        && args.similar.is_none()
+        && !args.directories;
+    if args.format.is_rich() && !is_file_scan {
+        anyhow::bail!(
+            "{:?} output is only available for duplicate files",
+            args.format
+        );
+    }
+    if let Some(path) = &args.known {
+        let found = config.find_known::<H>(&read_known(path)?);
+        // Every match is worth reporting, not only the duplicated ones.
+        args.rfactor.get_or_insert(ReplicationFactor::Over(0));
+        return write_output(args, found);
+    }
+    // Known contents are left out once the scan is done, whichever it is.
+    let known = args.ignore_known.as_deref().map(read_known).transpose()?;
+    let drop_known = |bag| match &known {
+        Some(known) => config.drop_known::<H>(bag, known),
+        None => bag,
+    };
+    if !args.find_copies_of.is_empty() {
+        let copies = drop_known(config.clone().find_copies_of::<H, _>(&args.find_copies_of));
+        return write_output(args, copies);
+    }
+    if !args.missing_from.is_empty() {
+        let missing = config.scan_missing_from::<H, _>(&args.missing_from);
+        let unverified = missing.unverified.len();
+        write_paths(args, missing.missing)?;
+        if unverified > 0 {
+            anyhow::bail!(
+                "{unverified} source files couldn't be read, whether they have a copy is unknown"
+            );
+        }
+        return Ok(());
+    }
+    if let Some(Ratio(threshold)) = args.similar {
+        let files = drop_known(config.clone().scan::<H>());
+        let overlaps = yadf::directory_overlaps(&files, &args.paths, threshold);
+        write_overlaps(args, overlaps)
+    } else if args.directories {
+        let files = drop_known(config.clone().scan::<H>());
+        let directories = yadf::group_directories(&files, &args.paths);
+        write_directories(args, directories)
+    } else {
+        let bag = match &args.manifest {
+            Some(path) => {
+                let (bag, manifest) = config.clone().scan_with_manifest::<H>();
+                write_manifest(path, &manifest)?;
+                bag
             }
-            Algorithm::XxHash => write_output::<twox_hash::XxHash3_128>(
-                args,
-                config.scan::<twox_hash::XxHash3_128>(),
-            )?,
+            None => config.clone().scan::<H>(),
+        };
+        let files = bag.as_inner().values().map(Vec::len).sum();
+        let bag = if args.every_file {
+            args.rfactor = Some(ReplicationFactor::Over(0));
+            config.hash_singletons::<H>(bag)
+        } else {
+            bag
+        };
+        let bag = drop_known(bag);
+        if let Format::Report = args.format {
+            write_report(args, started, files, bag)
+        } else {
+            write_output(args, bag)
         }
-        Ok(())
     }
 }
 
+fn write_manifest(path: &std::path::Path, manifest: &yadf::Manifest) -> anyhow::Result<()> {
+    let context = || format!("writing the manifest: {:?}", path.display());
+    let file = File::create(path).with_context(context)?;
+    let mut writer = io::BufWriter::new(file);
+    manifest.write_to(&mut writer).with_context(context)?;
+    writer.flush().with_context(context)?;
+    log::debug!("wrote {} hashes to {:?}", manifest.len(), path);
+    Ok(())
+}
+
+/// A scan of the configured paths.
+struct Scan<P: AsRef<std::path::Path>> {
+    args: Args,
+    config: yadf::Yadf<P>,
+}
+
+impl<P> WithHasher for Scan<P>
+where
+    P: AsRef<std::path::Path> + Clone,
+{
+    type Output = anyhow::Result<()>;
+
+    fn run<H>(self) -> anyhow::Result<()>
+    where
+        H: yadf::Hasher,
+        H::Hash: std::fmt::Debug + yadf::HashBytes,
+    {
+        scan::<H, P>(self.args, self.config)
+    }
+}
+
+fn run<P>(algorithm: Algorithm, args: Args, config: yadf::Yadf<P>) -> anyhow::Result<()>
+where
+    P: AsRef<std::path::Path> + Clone,
+{
+    log::debug!("using {:?} hashing", algorithm);
+    if (args.known.is_some() || args.ignore_known.is_some()) && !algorithm.is_stable() {
+        anyhow::bail!(
+            "{:?} hashes change from one run to the next, \
+            they can't be compared with known hashes",
+            algorithm
+        );
+    }
+    if args.manifest.is_some() && !algorithm.is_stable() {
+        anyhow::bail!(
+            "{:?} hashes change from one run to the next, \
+            they can't make a manifest",
+            algorithm
+        );
+    }
+    if args.with_hashes && !algorithm.is_stable() {
+        anyhow::bail!(
+            "{:?} hashes change from one run to the next, \
+            they can't identify a group",
+            algorithm
+        );
+    }
+    algorithm.dispatch(Scan { args, config })
+}
+
 impl Format {
-    fn display<W, H>(
+    fn display<W, K>(
         &self,
         writer: W,
-        replicates: yadf::FileReplicates<'_, H::Hash>,
+        replicates: yadf::FileReplicates<'_, K>,
     ) -> anyhow::Result<()>
     where
-        H: yadf::Hasher,
         W: Write,
     {
         let mut writer = io::BufWriter::with_capacity(64 * 1024, writer);
@@ -122,10 +337,225 @@ impl Format {
                 serde_json::to_writer_pretty(&mut writer, &replicates)?;
                 writer.write_all(b"\n")?;
             }
-            Format::Csv => csv_to_writer::<_, H>(writer, &replicates)?,
-            Format::LdJson => ldjson_to_writer::<_, H>(writer, &replicates)?,
+            Format::Csv => csv_to_writer(writer, &replicates)?,
+            Format::LdJson => ldjson_to_writer(writer, &replicates)?,
             Format::Fdupes => writeln!(writer, "{}", replicates.display::<Fdupes>())?,
             Format::Machine => writeln!(writer, "{}", replicates.display::<Machine>())?,
+            Format::JsonRich | Format::CsvRich | Format::Report => {
+                unreachable!("{self:?} output goes through display_rich or write_report")
+            }
+        };
+        Ok(())
+    }
+
+    fn display_with_hashes<W, K>(
+        &self,
+        writer: W,
+        replicates: yadf::FileReplicates<'_, K>,
+    ) -> anyhow::Result<()>
+    where
+        W: Write,
+        K: yadf::HashBytes,
+    {
+        let mut writer = io::BufWriter::with_capacity(64 * 1024, writer);
+        let groups = replicates.with_hashes();
+        match self {
+            Format::Json => {
+                serde_json::to_writer(&mut writer, &groups)?;
+                writer.write_all(b"\n")?;
+            }
+            Format::JsonPretty => {
+                serde_json::to_writer_pretty(&mut writer, &groups)?;
+                writer.write_all(b"\n")?;
+            }
+            Format::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .flexible(true)
+                    .has_headers(false)
+                    .from_writer(writer);
+                writer.serialize(("hash", "count", "files"))?;
+                for group in groups.iter() {
+                    writer.serialize((group.hash.hex(), group.files.len(), group.files))?;
+                }
+            }
+            Format::LdJson => {
+                for group in groups.iter() {
+                    serde_json::to_writer(&mut writer, &group)?;
+                    writeln!(writer)?;
+                }
+            }
+            Format::Fdupes => {
+                for (i, group) in groups.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(writer)?;
+                    }
+                    writeln!(writer, "{}", group.hash.hex())?;
+                    for path in group.files {
+                        writeln!(writer, "{}", path.as_ref().display())?;
+                    }
+                }
+            }
+            Format::Machine => {
+                for group in groups.iter() {
+                    write!(writer, "{}", group.hash.hex())?;
+                    for path in group.files {
+                        write!(writer, " {:?}", path.as_ref())?;
+                    }
+                    writeln!(writer)?;
+                }
+            }
+            Format::JsonRich | Format::CsvRich | Format::Report => {
+                unreachable!("{self:?} output goes through display_rich or write_report")
+            }
+        };
+        Ok(())
+    }
+
+    /// Whether each file comes with its metadata.
+    fn is_rich(&self) -> bool {
+        matches!(self, Format::JsonRich | Format::CsvRich | Format::Report)
+    }
+
+    fn display_rich<W, K>(
+        &self,
+        writer: W,
+        replicates: yadf::FileReplicates<'_, K>,
+    ) -> anyhow::Result<()>
+    where
+        W: Write,
+        K: yadf::HashBytes,
+    {
+        let mut writer = io::BufWriter::with_capacity(64 * 1024, writer);
+        match self {
+            Format::CsvRich => {
+                let mut writer = csv::Writer::from_writer(writer);
+                writer.write_record([
+                    "hash", "size", "wasted", "path", "mtime", "dev", "ino", "nlink", "root",
+                ])?;
+                for group in replicates.with_hashes().iter() {
+                    for path in group.files {
+                        let info = path.info();
+                        writer.serialize((
+                            group.hash.hex(),
+                            group.size(),
+                            group.wasted(),
+                            path,
+                            info.and_then(|info| info.mtime),
+                            info.and_then(|info| info.id).map(|id| id.0),
+                            info.and_then(|info| info.id).map(|id| id.1),
+                            info.and_then(|info| info.nlink),
+                            info.map(|info| info.root),
+                        ))?;
+                    }
+                }
+            }
+            Format::JsonRich => {
+                serde_json::to_writer(&mut writer, &replicates.rich())?;
+                writer.write_all(b"\n")?;
+            }
+            _ => unreachable!("{self:?} output doesn't go through display_rich"),
+        };
+        Ok(())
+    }
+
+    fn paths<W>(&self, writer: W, paths: &[yadf::Path]) -> anyhow::Result<()>
+    where
+        W: Write,
+    {
+        let mut writer = io::BufWriter::with_capacity(64 * 1024, writer);
+        match self {
+            Format::Json => {
+                serde_json::to_writer(&mut writer, paths)?;
+                writer.write_all(b"\n")?;
+            }
+            Format::JsonPretty => {
+                serde_json::to_writer_pretty(&mut writer, paths)?;
+                writer.write_all(b"\n")?;
+            }
+            Format::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(writer);
+                writer.serialize(("file",))?;
+                for path in paths {
+                    writer.serialize((path,))?;
+                }
+            }
+            Format::LdJson => {
+                for path in paths {
+                    serde_json::to_writer(&mut writer, path)?;
+                    writeln!(writer)?;
+                }
+            }
+            Format::Fdupes => {
+                for path in paths {
+                    writeln!(writer, "{}", path.as_ref().display())?;
+                }
+            }
+            Format::Machine => {
+                for path in paths {
+                    writeln!(writer, "{:?}", path.as_ref())?;
+                }
+            }
+            Format::JsonRich | Format::CsvRich | Format::Report => {
+                unreachable!("{self:?} output goes through display_rich or write_report")
+            }
+        };
+        Ok(())
+    }
+
+    fn overlaps<W>(&self, writer: W, overlaps: &[yadf::Overlap]) -> anyhow::Result<()>
+    where
+        W: Write,
+    {
+        let mut writer = io::BufWriter::with_capacity(64 * 1024, writer);
+        match self {
+            Format::Json => {
+                serde_json::to_writer(&mut writer, overlaps)?;
+                writer.write_all(b"\n")?;
+            }
+            Format::JsonPretty => {
+                serde_json::to_writer_pretty(&mut writer, overlaps)?;
+                writer.write_all(b"\n")?;
+            }
+            Format::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(writer);
+                writer.serialize(("relation", "similarity", "left", "right"))?;
+                for o in overlaps {
+                    writer.serialize((o.relation, o.similarity, &o.left, &o.right))?;
+                }
+            }
+            Format::LdJson => {
+                for overlap in overlaps {
+                    serde_json::to_writer(&mut writer, overlap)?;
+                    writeln!(writer)?;
+                }
+            }
+            Format::Fdupes => {
+                for o in overlaps {
+                    let (left, right) = (o.left.as_ref().display(), o.right.as_ref().display());
+                    writeln!(
+                        writer,
+                        "{} {:.3} {} {}",
+                        o.relation, o.similarity, left, right
+                    )?;
+                }
+            }
+            Format::Machine => {
+                for o in overlaps {
+                    let (left, right) = (o.left.as_ref(), o.right.as_ref());
+                    writeln!(
+                        writer,
+                        "{} {:.3} {:?} {:?}",
+                        o.relation, o.similarity, left, right
+                    )?;
+                }
+            }
+            Format::JsonRich | Format::CsvRich | Format::Report => {
+                unreachable!("{self:?} output goes through display_rich or write_report")
+            }
         };
         Ok(())
     }
@@ -133,13 +563,33 @@ impl Format {
 
 /// Yet Another Dupes Finder
 #[derive(Parser, Debug, Clone)]
+#[clap(args_conflicts_with_subcommands = true)]
 pub struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
     /// Directories to search
     ///
     /// If omitted and stdin is not a terminal, paths are read one per line
     /// from stdin; otherwise the default is the current working directory
     #[clap(value_parser)]
     paths: Vec<PathBuf>,
+    /// Scan exactly the files listed in this file, one per line
+    ///
+    /// Nothing is walked: each listed path is checked as it is, and those
+    /// which aren't files, links included, are left out. A file listed twice
+    /// is scanned once. Use `-` to read the list from stdin.
+    #[clap(
+        long,
+        value_name = "list",
+        conflicts_with_all = ["paths", "files0_from", "max_depth"]
+    )]
+    files_from: Option<PathBuf>,
+    /// Like `--files-from`, for a list separated by NUL characters
+    ///
+    /// Such as the output of `find -print0` or `fd -0`, for paths holding
+    /// newlines.
+    #[clap(long, value_name = "list", conflicts_with_all = ["paths", "max_depth"])]
+    files0_from: Option<PathBuf>,
     /// Output format
     #[clap(short, long, value_enum, default_value_t, ignore_case = true)]
     format: Format,
@@ -165,6 +615,22 @@ pub struct Args {
     /// help on spinning disks. The optimum is storage-dependent.
     #[clap(long, value_name = "n")]
     io_threads: Option<usize>,
+    /// Read the first and last blocks of files through io_uring
+    ///
+    /// Opens, reads and closes whole batches of files in a few system calls,
+    /// keeping the device queue deep without the prefetching threads. Falls
+    /// back to the usual reads where io_uring is unavailable (not Linux, an
+    /// old kernel, or a sandbox forbidding it).
+    #[clap(long)]
+    io_uring: bool,
+    /// Fetch the metadata of files in batches while walking
+    ///
+    /// Rather than one `stat` after the other on each walker thread, which
+    /// is what the walk spends its time on where each one waits on the
+    /// network, such as on NFS. Through io_uring along with `--io-uring`,
+    /// on a few threads at once otherwise.
+    #[clap(long)]
+    batch_stat: bool,
     /// Treat hard links to same file as duplicates
     #[cfg_attr(unix, clap(short = 'H', long))]
     #[cfg(unix)]
@@ -185,32 +651,198 @@ pub struct Args {
     /// to find files with less than 10 copies use `under:10`
     #[clap(long)]
     rfactor: Option<ReplicationFactor>,
+    /// Report whole directories with identical contents instead of files
+    ///
+    /// Two directories match when they hold the same names with the same
+    /// contents, recursively. Only the highest matching directories are
+    /// reported: subdirectories of a match are implied.
+    #[clap(short = 'D', long)]
+    directories: bool,
+    /// Report directory pairs sharing contents instead of files
+    ///
+    /// Pairs where one directory holds everything the other does are always
+    /// reported, others only if at least this similar (a ratio between 0 and
+    /// 1 of shared contents over all contents). Names don't matter here, so
+    /// a reorganised copy still counts. A ratio of 1 only reports containment.
+    #[clap(long, value_name = "ratio", conflicts_with = "directories")]
+    similar: Option<Ratio>,
+    /// Report files whose content has no copy under this directory
+    ///
+    /// Checks a backup: lists every file under PATHS whose content is found
+    /// nowhere under the given destinations, whatever its name or location
+    /// there. Can be repeated to check against several destinations. Fails
+    /// if a source file couldn't be read.
+    #[clap(
+        long,
+        value_name = "dest",
+        conflicts_with_all = ["directories", "similar", "rfactor"]
+    )]
+    missing_from: Vec<PathBuf>,
+    /// Report the copies of this file instead of every duplicate
+    ///
+    /// Only this file and the files sharing its size are read, so looking
+    /// for a large file is far cheaper than a full scan. Each group starts
+    /// with the file looked for. Can be repeated to look for several files.
+    #[clap(
+        long,
+        value_name = "file",
+        conflicts_with_all = ["directories", "similar", "missing_from"]
+    )]
+    find_copies_of: Vec<PathBuf>,
+    /// Report the files whose content hash is in this checksum list
+    ///
+    /// The list holds one hex digest per line, such as the output of
+    /// `sha256sum` or `b3sum`, and must have been made with the same
+    /// algorithm. Every file is read in full.
+    #[clap(
+        long,
+        value_name = "list",
+        conflicts_with_all = ["directories", "similar", "missing_from", "find_copies_of"]
+    )]
+    known: Option<PathBuf>,
+    /// Leave out the duplicates whose content hash is in this checksum list
+    ///
+    /// Same list format as `--known`, for files known to be copied on
+    /// purpose, like a standard license file.
+    #[clap(long, value_name = "list", conflicts_with_all = ["known", "missing_from"])]
+    ignore_known: Option<PathBuf>,
+    /// Include the hash of each group in the output
+    ///
+    /// Hex-encoded, it identifies a group from one run to the next, which
+    /// `ahash` can't do and is refused for. Files of at least 4 KiB are
+    /// grouped by the hash of their whole content; smaller ones by a hash
+    /// that also covers their size, and files without a duplicate by cheaper
+    /// partial hashes.
+    #[clap(long, conflicts_with_all = ["directories", "similar", "missing_from"])]
+    with_hashes: bool,
+    /// List every file, each under a hash of its whole content
+    ///
+    /// Files without a duplicate are hashed in full too, so that reports
+    /// made on different hosts with `--format report` can be combined by
+    /// `yadf merge`, which needs to see the contents held once on a host.
+    #[clap(
+        long,
+        conflicts_with_all = ["directories", "similar", "missing_from", "find_copies_of", "known", "rfactor"]
+    )]
+    every_file: bool,
+    /// Also write the hash of every file read in full to this file
+    ///
+    /// One `<hash>  <path>` line per file, which `sha256sum -c` (or `b3sum
+    /// -c`, and so on) can check when the same algorithm is used. Only files
+    /// that could be duplicates are read in full, and those under 4 KiB are
+    /// left out.
+    #[clap(
+        long,
+        value_name = "file",
+        conflicts_with_all = ["directories", "similar", "missing_from", "find_copies_of", "known"]
+    )]
+    manifest: Option<PathBuf>,
     /// Optional output file
     #[clap(short, long)]
     output: Option<PathBuf>,
 }
 
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Check whether the groups of an earlier report still hold
+    ///
+    /// Reads again every file listed in a report written with `--format
+    /// report`, using the same algorithm, then tells which groups still hold,
+    /// which split apart, and which files disappeared.
+    Verify {
+        /// The report to check
+        report: PathBuf,
+        /// Print the result as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Check the files listed in a manifest against their hashes
+    ///
+    /// Reads a manifest written with `--manifest`, or by `sha256sum` and the
+    /// like, and tells which files are unchanged, modified or missing.
+    Check {
+        /// The manifest to check
+        manifest: PathBuf,
+        /// Hashing algorithm the manifest was made with
+        #[clap(short, long, value_enum, default_value_t, ignore_case = true)]
+        algorithm: Algorithm,
+    },
+    /// Hash every file in full, for a later `yadf diff`
+    ///
+    /// Writes one `<hash>  <path>` line per file, like `sha256sum`. Unlike a
+    /// scan, every file is read, and every hard link is listed.
+    Snapshot {
+        /// Directories to hash
+        #[clap(required = true)]
+        paths: Vec<PathBuf>,
+        /// Hashing algorithm
+        #[clap(short, long, value_enum, default_value_t, ignore_case = true)]
+        algorithm: Algorithm,
+        /// Optional output file
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Tell which files were moved, copied, added, deleted or modified since
+    /// a snapshot or a report
+    ///
+    /// Hashes every file under the paths again and compares them with the
+    /// snapshot, by path and by content. The paths must be given the same
+    /// way as when the snapshot was taken, relative or absolute.
+    ///
+    /// A report written with `--format report --every-file` lists every file
+    /// too, and can stand in for a snapshot: it is compared with the files
+    /// hashed the way it hashed them, with the algorithm it was made with.
+    /// Only its hard link setting is applied again, not its other filters.
+    /// Other reports and `--manifest` files leave out the files without a
+    /// duplicate, which would all show as added.
+    Diff {
+        /// The snapshot to compare with, from `yadf snapshot` or `--save`,
+        /// or an `--every-file` report
+        snapshot: PathBuf,
+        /// Directories the snapshot was taken of
+        #[clap(required = true)]
+        paths: Vec<PathBuf>,
+        /// Hashing algorithm the snapshot was made with, a report telling
+        /// its own
+        #[clap(short, long, value_enum, default_value_t, ignore_case = true)]
+        algorithm: Algorithm,
+        /// Also save the new snapshot to this file, when comparing with a
+        /// snapshot
+        #[clap(long, value_name = "file")]
+        save: Option<PathBuf>,
+        /// Print the changes as JSON
+        #[clap(long)]
+        json: bool,
+    },
+    /// Find the contents duplicated across the hosts of several reports
+    ///
+    /// Combines reports written with `--format report --every-file` on
+    /// different hosts, all with the same algorithm, and lists the contents
+    /// found on more than one of them. For each host, tells how many bytes it
+    /// would free by deleting its copies of those contents while the other
+    /// hosts keep theirs.
+    Merge {
+        /// The reports, each labelled with its host, or else with its name
+        #[clap(required = true, num_args = 2.., value_name = "[host=]report")]
+        reports: Vec<merge::HostReport>,
+        /// Print the result as JSON
+        #[clap(long)]
+        json: bool,
+    },
+}
+
 #[derive(ValueEnum, Debug, Clone, Default)]
 enum Format {
     Csv,
+    CsvRich,
     #[default]
     Fdupes,
     Json,
     JsonPretty,
+    JsonRich,
     LdJson,
     Machine,
-}
-
-#[derive(ValueEnum, Debug, Clone, Default)]
-#[clap(rename_all = "lower")]
-enum Algorithm {
-    AHash,
-    Blake3,
-    #[default]
-    Highway,
-    MetroHash,
-    SeaHash,
-    XxHash,
+    Report,
 }
 
 #[derive(Debug, Clone)]
@@ -225,6 +857,20 @@ impl FromStr for Byte {
     }
 }
 
+/// A fraction between 0 and 1.
+#[derive(Debug, Clone, Copy)]
+struct Ratio(f64);
+
+impl FromStr for Ratio {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(ratio) if (0.0..=1.0).contains(&ratio) => Ok(Ratio(ratio)),
+            _ => Err(format!("expected a ratio between 0 and 1, got {s:?}")),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 enum ReplicationFactor {
     Under(usize),
@@ -233,9 +879,8 @@ enum ReplicationFactor {
 }
 
 /// mimic serde_json interface
-fn csv_to_writer<W, H>(writer: W, replicates: &yadf::FileReplicates<'_, H::Hash>) -> csv::Result<()>
+fn csv_to_writer<W, K>(writer: W, replicates: &yadf::FileReplicates<'_, K>) -> csv::Result<()>
 where
-    H: yadf::Hasher,
     W: Write,
 {
     let mut writer = csv::WriterBuilder::new()
@@ -250,12 +895,11 @@ where
 }
 
 /// mimic serde_json interface
-fn ldjson_to_writer<W, H>(
+fn ldjson_to_writer<W, K>(
     mut writer: W,
-    replicates: &yadf::FileReplicates<'_, H::Hash>,
+    replicates: &yadf::FileReplicates<'_, K>,
 ) -> anyhow::Result<()>
 where
-    H: yadf::Hasher,
     W: Write,
 {
     for files in replicates {
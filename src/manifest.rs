@@ -0,0 +1,403 @@
+//! Full content hashes of files, one `<hex digest>  <path>` line each, the
+//! way `sha256sum`, `b3sum` and the like write them.
+//!
+//! Written by a scan for the files it read whole, a manifest can be checked
+//! later by `sha256sum -c` when the algorithms match, or by
+//! [`Manifest::check`].
+
+use crate::hasher::{HashBytes, Hasher};
+use crate::known::decode_hex;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// A list of files and their full content hashes.
+#[derive(Debug, Default)]
+pub struct Manifest(Vec<(Vec<u8>, PathBuf)>);
+
+/// What became of a file listed in a [`Manifest`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Unchanged,
+    Modified,
+    Missing,
+    /// The file is there but couldn't be read.
+    Unreadable,
+}
+
+/// How a file changed between two manifests of the same tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change<'a> {
+    /// A new path, with a content found nowhere before.
+    Added(&'a Path),
+    /// A path gone, and its content with it.
+    Deleted(&'a Path),
+    /// The same path, with another content.
+    Modified(&'a Path),
+    /// A path gone, or changed, and its content found under a new one, with
+    /// no path still holding it: moved or renamed.
+    Moved { from: &'a Path, to: &'a Path },
+    /// A new path, with a content found before under a path still there.
+    Copied { from: &'a Path, to: &'a Path },
+}
+
+impl Manifest {
+    /// Reads `sha256sum`-style lines, binary-mode `*` markers and escaped
+    /// names included, or BSD-style tagged lines. Blank lines and lines
+    /// starting with `#` are skipped.
+    pub fn read<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut manifest = Self::default();
+        for (number, line) in reader.split(b'\n').enumerate() {
+            let line = line?;
+            let line = line.strip_suffix(b"\r").unwrap_or(&line);
+            if line.is_empty() || line.starts_with(b"#") {
+                continue;
+            }
+            let entry = parse_line(line).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: not a checksum line", number + 1),
+                )
+            })?;
+            manifest.0.push(entry);
+        }
+        Ok(manifest)
+    }
+
+    /// Writes one line per file, escaping names the way `sha256sum` does so
+    /// that `sha256sum -c` reads them back.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for (digest, path) in &self.0 {
+            let name = path_bytes(path);
+            let needs_escape = name.iter().any(|b| matches!(b, b'\\' | b'\n' | b'\r'));
+            if needs_escape {
+                writer.write_all(b"\\")?;
+            }
+            for byte in digest {
+                write!(writer, "{byte:02x}")?;
+            }
+            writer.write_all(b"  ")?;
+            if needs_escape {
+                for &byte in name.iter() {
+                    match byte {
+                        b'\\' => writer.write_all(b"\\\\")?,
+                        b'\n' => writer.write_all(b"\\n")?,
+                        b'\r' => writer.write_all(b"\\r")?,
+                        byte => writer.write_all(&[byte])?,
+                    }
+                }
+            } else {
+                writer.write_all(&name)?;
+            }
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// A manifest of `entries`, given with hex digests as a report lists
+    /// them, or `None` if one of the digests isn't hex.
+    pub fn from_hex<'a, I>(entries: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = (&'a str, PathBuf)>,
+    {
+        let mut entries = entries
+            .into_iter()
+            .map(|(digest, path)| Some((decode_hex(digest)?, path)))
+            .collect::<Option<Vec<_>>>()?;
+        entries.sort_by(|a, b| a.1.cmp(&b.1));
+        Some(Self(entries))
+    }
+
+    /// Every file of `bag`, under the hash it is kept by.
+    pub(crate) fn from_bag<K: HashBytes>(bag: crate::FileCounter<K>) -> Self {
+        let mut entries: Vec<_> = bag
+            .into_inner()
+            .into_iter()
+            .flat_map(|(hash, bucket)| {
+                let digest = hash.to_bytes();
+                bucket
+                    .into_iter()
+                    .map(move |path| (digest.clone(), path.as_ref().to_path_buf()))
+            })
+            .collect();
+        entries.sort_by(|a, b| a.1.cmp(&b.1));
+        Self(entries)
+    }
+
+    /// The listed files, with their digests.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &Path)> {
+        self.0
+            .iter()
+            .map(|(digest, path)| (digest.as_slice(), path.as_path()))
+    }
+
+    /// Returns the number of files listed.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if no file is listed.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// What changed from this manifest to `newer`, file by file, as seen by
+    /// their paths and contents alone.
+    ///
+    /// When several paths disappear and reappear with the same content, they
+    /// are paired in path order. A new path whose content is still where it
+    /// was is a copy of it; if it isn't anywhere it was any more, the new path
+    /// is a second home of a moved content. Paths are compared as written, so
+    /// both manifests must have been made from the same paths.
+    pub fn diff<'a>(&'a self, newer: &'a Manifest) -> Vec<Change<'a>> {
+        let before: HashMap<&Path, &[u8]> = self.iter().map(|(d, p)| (p, d)).collect();
+        let after: HashMap<&Path, &[u8]> = newer.iter().map(|(d, p)| (p, d)).collect();
+        let mut changes = Vec::new();
+        // Paths gone, by the content they held, to be paired with new paths.
+        let mut gone: BTreeMap<&[u8], Vec<&Path>> = BTreeMap::new();
+        // The first path of each content, and the first still holding it.
+        let mut held: HashMap<&[u8], &Path> = HashMap::new();
+        let mut kept: HashMap<&[u8], &Path> = HashMap::new();
+        for (digest, path) in self.iter() {
+            held.entry(digest).or_insert(path);
+            match after.get(path) {
+                Some(&now) if now != digest => changes.push(Change::Modified(path)),
+                Some(_) => {
+                    kept.entry(digest).or_insert(path);
+                }
+                None => gone.entry(digest).or_default().push(path),
+            }
+        }
+        for paths in gone.values_mut() {
+            paths.sort();
+            paths.reverse();
+        }
+        let mut appeared: Vec<_> = newer
+            .iter()
+            .filter(|(_, path)| !before.contains_key(path))
+            .collect();
+        appeared.sort_by_key(|&(_, path)| path);
+        for (digest, to) in appeared {
+            let change = match gone.get_mut(digest).and_then(Vec::pop) {
+                Some(from) => Change::Moved { from, to },
+                None => match (kept.get(digest), held.get(digest)) {
+                    (Some(&from), _) => Change::Copied { from, to },
+                    (None, Some(&from)) => Change::Moved { from, to },
+                    (None, None) => Change::Added(to),
+                },
+            };
+            changes.push(change);
+        }
+        changes.extend(gone.into_values().flatten().map(Change::Deleted));
+        changes.sort_by_key(|change| match *change {
+            Change::Added(path) | Change::Deleted(path) | Change::Modified(path) => path,
+            Change::Moved { to, .. } | Change::Copied { to, .. } => to,
+        });
+        changes
+    }
+
+    /// Hashes every listed file again with `H` and tells what became of it,
+    /// in the manifest's order.
+    pub fn check<H>(&self, io_threads: usize) -> Vec<(&Path, FileStatus)>
+    where
+        H: Hasher,
+        H::Hash: HashBytes,
+    {
+        crate::fs::check::<H>(&crate::vfs::Os::default(), self, io_threads)
+    }
+}
+
+/// Sorted by path, so two manifests of the same tree compare line by line.
+impl<K: HashBytes> FromIterator<(K, PathBuf)> for Manifest {
+    fn from_iter<I>(entries: I) -> Self
+    where
+        I: IntoIterator<Item = (K, PathBuf)>,
+    {
+        let mut entries: Vec<_> = entries
+            .into_iter()
+            .map(|(hash, path)| (hash.to_bytes(), path))
+            .collect();
+        entries.sort_by(|a, b| a.1.cmp(&b.1));
+        Self(entries)
+    }
+}
+
+fn parse_line(line: &[u8]) -> Option<(Vec<u8>, PathBuf)> {
+    parse_gnu(line).or_else(|| parse_tagged(line))
+}
+
+/// `digest  path`, or `digest *path`
+fn parse_gnu(line: &[u8]) -> Option<(Vec<u8>, PathBuf)> {
+    let (escaped, line) = match line.strip_prefix(b"\\") {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+    let split = line.iter().position(|&b| b == b' ')?;
+    let (digest, rest) = line.split_at(split);
+    // One space, then a space for text mode or `*` for binary mode.
+    let name = rest
+        .get(2..)
+        .filter(|_| matches!(rest.get(1), Some(b' ' | b'*')))?;
+    let digest = decode_hex(std::str::from_utf8(digest).ok()?)?;
+    let name = if escaped {
+        unescape(name)?
+    } else {
+        name.to_vec()
+    };
+    Some((digest, path_from_bytes(name)))
+}
+
+/// `SHA256 (path) = digest`
+fn parse_tagged(line: &[u8]) -> Option<(Vec<u8>, PathBuf)> {
+    let open = line.iter().position(|&b| b == b'(')?;
+    let close = line.windows(4).rposition(|w| w == b") = ")?;
+    let name = line.get(open + 1..close)?;
+    let digest = decode_hex(std::str::from_utf8(&line[close + 4..]).ok()?)?;
+    Some((digest, path_from_bytes(name.to_vec())))
+}
+
+fn unescape(name: &[u8]) -> Option<Vec<u8>> {
+    let mut unescaped = Vec::with_capacity(name.len());
+    let mut bytes = name.iter();
+    while let Some(&byte) = bytes.next() {
+        unescaped.push(match byte {
+            b'\\' => match bytes.next()? {
+                b'\\' => b'\\',
+                b'n' => b'\n',
+                b'r' => b'\r',
+                _ => return None,
+            },
+            byte => byte,
+        });
+    }
+    Some(unescaped)
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().into()
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    match path.to_string_lossy() {
+        std::borrow::Cow::Borrowed(name) => name.as_bytes().into(),
+        std::borrow::Cow::Owned(name) => name.into_bytes().into(),
+    }
+}
+
+#[cfg(unix)]
+fn path_from_bytes(name: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    std::ffi::OsString::from_vec(name).into()
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(name: Vec<u8>) -> PathBuf {
+    String::from_utf8_lossy(&name).into_owned().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_what_it_reads() {
+        let manifest: Manifest = [
+            (0xffu64, PathBuf::from("plain name")),
+            (0xfe, PathBuf::from("odd\nname\\")),
+        ]
+        .into_iter()
+        .collect();
+        let mut written = Vec::new();
+        manifest.write_to(&mut written).unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&written),
+            "\\00000000000000fe  odd\\nname\\\\\n00000000000000ff  plain name\n"
+        );
+        let read = Manifest::read(written.as_slice()).unwrap();
+        assert_eq!(read.0, manifest.0);
+    }
+
+    #[test]
+    fn tells_moves_from_copies() {
+        let manifest = |entries: &[(u64, &str)]| -> Manifest {
+            entries
+                .iter()
+                .map(|&(hash, path)| (hash, PathBuf::from(path)))
+                .collect()
+        };
+        let before = manifest(&[(1, "kept"), (2, "edited"), (3, "old name"), (4, "removed")]);
+        let after = manifest(&[
+            (1, "kept"),
+            (1, "kept copy"),
+            (5, "edited"),
+            (3, "new name"),
+            (6, "new"),
+        ]);
+        let path = Path::new;
+        assert_eq!(
+            before.diff(&after),
+            [
+                Change::Modified(path("edited")),
+                Change::Copied {
+                    from: path("kept"),
+                    to: path("kept copy")
+                },
+                Change::Added(path("new")),
+                Change::Moved {
+                    from: path("old name"),
+                    to: path("new name")
+                },
+                Change::Deleted(path("removed")),
+            ]
+        );
+    }
+
+    #[test]
+    fn copies_come_from_a_path_still_holding_the_content() {
+        let manifest = |entries: &[(u64, &str)]| -> Manifest {
+            entries
+                .iter()
+                .map(|&(hash, path)| (hash, PathBuf::from(path)))
+                .collect()
+        };
+        let before = manifest(&[(1, "a"), (1, "b"), (2, "c")]);
+        let after = manifest(&[(3, "a"), (1, "b"), (1, "b copy"), (2, "d"), (2, "e")]);
+        let path = Path::new;
+        assert_eq!(
+            before.diff(&after),
+            [
+                Change::Modified(path("a")),
+                Change::Copied {
+                    from: path("b"),
+                    to: path("b copy")
+                },
+                Change::Moved {
+                    from: path("c"),
+                    to: path("d")
+                },
+                Change::Moved {
+                    from: path("c"),
+                    to: path("e")
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_binary_mode_and_tagged_lines() {
+        let list = "00ff *bin/file\nSHA256 (tagged (1)) = 00fe\n";
+        let manifest = Manifest::read(list.as_bytes()).unwrap();
+        let entries: Vec<_> = manifest.iter().collect();
+        assert_eq!(
+            entries,
+            [
+                (&[0x00, 0xff][..], Path::new("bin/file")),
+                (&[0x00, 0xfe][..], Path::new("tagged (1)")),
+            ]
+        );
+        assert!(Manifest::read("00ff\n".as_bytes()).is_err());
+    }
+}
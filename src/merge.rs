@@ -0,0 +1,202 @@
+//! `yadf merge`: reports made on several hosts, combined to find the contents
+//! duplicated across them.
+
+use super::report::Loaded;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// A report and the host it was made on, written `host=report.json`, or
+/// `report.json` to label it with the file's stem.
+#[derive(Debug, Clone)]
+pub struct HostReport {
+    host: String,
+    path: PathBuf,
+}
+
+impl FromStr for HostReport {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            // A report whose name holds a `=` is taken as it is.
+            Some((host, path)) if !host.is_empty() && !Path::new(s).exists() => Ok(Self {
+                host: host.to_owned(),
+                path: path.into(),
+            }),
+            _ => {
+                let path = PathBuf::from(s);
+                let host = path
+                    .file_stem()
+                    .ok_or_else(|| format!("{s:?} has no name to label it with"))?
+                    .to_string_lossy()
+                    .into_owned();
+                Ok(Self { host, path })
+            }
+        }
+    }
+}
+
+/// The contents found on more than one host.
+#[derive(Debug, Serialize)]
+struct Merged<'a> {
+    algorithm: &'a str,
+    groups: Vec<Group<'a>>,
+    hosts: Vec<Host<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Group<'a> {
+    hash: &'a str,
+    size: Option<u64>,
+    files: Vec<File<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct File<'a> {
+    host: &'a str,
+    path: &'a yadf::Path,
+}
+
+/// What a host holds of the contents found elsewhere too.
+#[derive(Debug, Serialize)]
+struct Host<'a> {
+    host: &'a str,
+    /// Its copies of those contents.
+    files: usize,
+    /// The bytes it would free by deleting them, the other hosts keeping
+    /// theirs.
+    freeable: u64,
+}
+
+pub fn run(reports: &[HostReport], json: bool) -> anyhow::Result<()> {
+    let mut hosts = BTreeSet::new();
+    if let Some(report) = reports.iter().find(|report| !hosts.insert(&report.host)) {
+        anyhow::bail!("two reports are labelled {:?}", report.host);
+    }
+    let mut algorithm = None;
+    let mut sizes = HashMap::new();
+    let mut bag = yadf::TreeBag::default();
+    for report in reports {
+        let loaded = Loaded::read(&report.path)?;
+        if !loaded.algorithm()?.is_stable() {
+            anyhow::bail!(
+                "{:?} was made with {:?}, its hashes can't be compared with another run's",
+                report.path.display(),
+                loaded.algorithm
+            );
+        }
+        if !loaded.every_file {
+            anyhow::bail!(
+                "{:?} only lists duplicates, a content held once on a host would be missed: \
+                make it with `--every-file`",
+                report.path.display()
+            );
+        }
+        match &algorithm {
+            Some(first) if *first != loaded.algorithm => anyhow::bail!(
+                "{:?} was made with {:?}, not {:?} like the reports before it",
+                report.path.display(),
+                loaded.algorithm,
+                first
+            ),
+            Some(_) => {}
+            None => algorithm = Some(loaded.algorithm.clone()),
+        }
+        let host = report.host.as_str();
+        let mut files = yadf::TreeBag::default();
+        for group in loaded.groups {
+            if let Some(size) = group.size {
+                sizes.insert(group.hash.clone(), size);
+            }
+            let hash = group.hash;
+            files.extend(
+                group
+                    .files
+                    .into_iter()
+                    .map(|file| (hash.clone(), (host, file.path))),
+            );
+        }
+        bag = bag.merge(files);
+    }
+    let algorithm = algorithm.unwrap_or_default();
+    let merged = merge(&algorithm, &hosts, &bag, &sizes);
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    if json {
+        serde_json::to_writer(&mut stdout, &merged)?;
+        writeln!(stdout)?;
+    } else {
+        merged.write_text(&mut stdout)?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+fn merge<'a>(
+    algorithm: &'a str,
+    hosts: &BTreeSet<&'a String>,
+    bag: &'a yadf::TreeBag<String, (&'a str, yadf::Path)>,
+    sizes: &HashMap<String, u64>,
+) -> Merged<'a> {
+    let mut freeable: BTreeMap<&str, Host<'_>> = hosts
+        .iter()
+        .map(|host| {
+            let host = host.as_str();
+            let nothing = Host {
+                host,
+                files: 0,
+                freeable: 0,
+            };
+            (host, nothing)
+        })
+        .collect();
+    let mut groups = Vec::new();
+    for (hash, files) in bag.as_inner() {
+        let on: BTreeSet<_> = files.iter().map(|(host, _)| host).collect();
+        if on.len() < 2 {
+            continue;
+        }
+        let size = sizes.get(hash).copied();
+        for &(host, _) in files {
+            if let Some(host) = freeable.get_mut(host) {
+                host.files += 1;
+                host.freeable += size.unwrap_or_default();
+            }
+        }
+        let files = files
+            .iter()
+            .map(|(host, path)| File { host, path })
+            .collect();
+        groups.push(Group { hash, size, files });
+    }
+    Merged {
+        algorithm,
+        groups,
+        hosts: freeable.into_values().collect(),
+    }
+}
+
+impl Merged<'_> {
+    /// One block per content, its hash followed by `host: path` lines, then
+    /// one line per host. Blocks are separated by blank lines, like fdupes'
+    /// output.
+    fn write_text<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for group in &self.groups {
+            writeln!(writer, "{}", group.hash)?;
+            for file in &group.files {
+                writeln!(writer, "{}: {}", file.host, file.path.as_ref().display())?;
+            }
+            writeln!(writer)?;
+        }
+        for host in &self.hosts {
+            writeln!(
+                writer,
+                "{}: {} files held elsewhere too, {} bytes freeable",
+                host.host, host.files, host.freeable
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,15 +1,152 @@
 /// Serialization wrapper for paths.
+///
+/// It can also carry what the walk learned about the file, when the scan was
+/// asked to keep it.
 #[derive(Debug)]
-pub struct Path(std::path::PathBuf);
+pub struct Path {
+    path: std::path::PathBuf,
+    info: Option<Box<FileInfo>>,
+}
+
+/// A file's metadata, as found while walking.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileInfo {
+    pub size: u64,
+    /// Last modification, in seconds since the Unix epoch.
+    pub mtime: Option<i64>,
+    /// `(dev, ino)`, which tells hard links apart. Unix only.
+    pub id: Option<(u64, u64)>,
+    /// Number of hard links to the file. Unix only.
+    pub nlink: Option<u64>,
+    /// Index of the scanned path the file was found under.
+    pub root: usize,
+}
+
+/// Serializes a [`Path`] along with its [`FileInfo`], fields missing from it
+/// being `null`.
+#[derive(Debug)]
+pub struct Detailed<'a>(pub &'a Path);
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+impl Path {
+    pub(crate) fn with_info(path: std::path::PathBuf, info: FileInfo) -> Self {
+        Self {
+            path,
+            info: Some(Box::new(info)),
+        }
+    }
 
-use serde::{Serialize, Serializer};
+    /// The file's metadata, if the scan kept it.
+    pub fn info(&self) -> Option<&FileInfo> {
+        self.info.as_deref()
+    }
+}
 
+impl FileInfo {
+    pub(crate) fn new(meta: &crate::vfs::Metadata, root: usize) -> Self {
+        Self {
+            size: meta.len,
+            mtime: meta.modified,
+            id: meta.id,
+            nlink: meta.nlink,
+            root,
+        }
+    }
+}
+
+/// A path is a string when it is valid UTF-8, and its raw bytes otherwise,
+/// an array of numbers in JSON, so that it reads back as the same path.
+/// Only Unix paths have bytes to give: elsewhere, a path which isn't valid
+/// Unicode is written lossily.
 impl Serialize for Path {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        serializer.collect_str(&self.0.display())
+        match self.path.to_str() {
+            Some(path) => serializer.serialize_str(path),
+            #[cfg(unix)]
+            None => {
+                use std::os::unix::ffi::OsStrExt;
+                serializer.serialize_bytes(self.path.as_os_str().as_bytes())
+            }
+            #[cfg(not(unix))]
+            None => serializer.collect_str(&self.path.display()),
+        }
+    }
+}
+
+impl Serialize for Detailed<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let info = self.0.info();
+        let mut file = serializer.serialize_struct("File", 7)?;
+        file.serialize_field("path", self.0)?;
+        file.serialize_field("size", &info.map(|info| info.size))?;
+        file.serialize_field("mtime", &info.and_then(|info| info.mtime))?;
+        file.serialize_field("dev", &info.and_then(|info| info.id).map(|id| id.0))?;
+        file.serialize_field("ino", &info.and_then(|info| info.id).map(|id| id.1))?;
+        file.serialize_field("nlink", &info.and_then(|info| info.nlink))?;
+        file.serialize_field("root", &info.map(|info| info.root))?;
+        file.end()
+    }
+}
+
+/// Reads a path back from its serialized form, a string or raw bytes. What
+/// the walk learned about the file isn't part of it.
+impl<'de> serde::Deserialize<'de> for Path {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(PathVisitor)
+    }
+}
+
+struct PathVisitor;
+
+impl<'de> serde::de::Visitor<'de> for PathVisitor {
+    type Value = Path;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str("a path, as a string or as bytes")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, path: &str) -> Result<Path, E> {
+        Ok(Path::from(path))
+    }
+
+    fn visit_string<E: serde::de::Error>(self, path: String) -> Result<Path, E> {
+        Ok(Path::from(path))
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, path: &[u8]) -> Result<Path, E> {
+        self.visit_byte_buf(path.to_vec())
+    }
+
+    fn visit_byte_buf<E: serde::de::Error>(self, path: Vec<u8>) -> Result<Path, E> {
+        #[cfg(unix)]
+        let path = {
+            use std::os::unix::ffi::OsStringExt;
+            std::ffi::OsString::from_vec(path)
+        };
+        #[cfg(not(unix))]
+        let path = String::from_utf8_lossy(&path).into_owned();
+        Ok(Path::from(path))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Path, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        self.visit_byte_buf(bytes)
     }
 }
 
@@ -18,13 +155,16 @@ where
     T: Into<std::path::PathBuf>,
 {
     fn from(path: T) -> Self {
-        Self(path.into())
+        Self {
+            path: path.into(),
+            info: None,
+        }
     }
 }
 
 impl AsRef<std::path::Path> for Path {
     fn as_ref(&self) -> &std::path::Path {
-        &self.0
+        &self.path
     }
 }
 
@@ -44,7 +184,35 @@ mod tests {
         let path = PathBuf::from(OsString::from_vec(invalid_utf8.to_vec()));
         assert!(serde_json::to_string(&path).is_err());
         // with wrapper it's ok
-        let path = Path(PathBuf::from(OsString::from_vec(invalid_utf8.to_vec())));
+        let path = Path::from(PathBuf::from(OsString::from_vec(invalid_utf8.to_vec())));
         assert!(serde_json::to_string(&path).is_ok());
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn invalid_utf8_path_reads_back_the_same() {
+        use super::*;
+        use std::ffi::OsString;
+        use std::os::unix::ffi::OsStringExt;
+        let name = b"dir/\xe7\xe7".to_vec();
+        let path = Path::from(OsString::from_vec(name.clone()));
+        let json = serde_json::to_string(&path).unwrap();
+        assert_eq!(json, "[100,105,114,47,231,231]");
+        let back: Path = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            back.as_ref(),
+            std::path::Path::new(&OsString::from_vec(name))
+        );
+        let back: Path = serde_json::from_str(r#""dir/foo""#).unwrap();
+        assert_eq!(back.as_ref(), std::path::Path::new("dir/foo"));
+    }
+
+    #[test]
+    fn detailed_fields_are_null_without_info() {
+        use super::*;
+        let path = Path::from("foo");
+        let result = serde_json::to_string(&Detailed(&path)).unwrap();
+        let expected = r#"{"path":"foo","size":null,"mtime":null,"dev":null,"ino":null,"nlink":null,"root":null}"#;
+        assert_eq!(result, expected);
+    }
 }
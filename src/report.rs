@@ -0,0 +1,190 @@
+//! The `report` output: the duplicate groups, wrapped with everything needed
+//! to make sense of them long after the scan.
+
+use super::{Args, ReplicationFactor};
+use anyhow::Context;
+use clap::ValueEnum;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::time::SystemTime;
+use yadf::Algorithm;
+
+/// A scan and its results.
+pub struct Report<'a, K> {
+    pub args: &'a Args,
+    pub started: SystemTime,
+    pub finished: SystemTime,
+    pub stats: Stats,
+    pub replicates: yadf::FileReplicates<'a, K>,
+}
+
+/// Totals over the whole scan, not only the groups reported.
+#[derive(Debug, Default)]
+pub struct Stats {
+    /// Files found by the walk.
+    pub files: usize,
+    /// Groups of files sharing a content.
+    pub groups: usize,
+    /// Files in those groups.
+    pub duplicates: usize,
+    /// Bytes taken by every copy but one in each group.
+    pub wasted: u64,
+}
+
+/// A report read back: only what is needed to check it against the files,
+/// or to merge it with others.
+#[derive(Debug, serde::Deserialize)]
+pub struct Loaded {
+    pub algorithm: String,
+    /// Whether every file is listed under a hash of its whole content.
+    #[serde(default)]
+    pub every_file: bool,
+    #[serde(default)]
+    pub filters: LoadedFilters,
+    pub groups: Vec<LoadedGroup>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct LoadedFilters {
+    /// Whether every hard link was listed, rather than one per file.
+    #[serde(default)]
+    pub hard_links: bool,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct LoadedGroup {
+    pub hash: String,
+    pub size: Option<u64>,
+    pub files: Vec<LoadedFile>,
+}
+
+#[derive(Debug, serde::Deserialize)]
+pub struct LoadedFile {
+    pub path: yadf::Path,
+}
+
+/// The options restricting which files were considered.
+struct Filters<'a>(&'a Args);
+
+impl Stats {
+    /// The totals of `bag`, out of the `files` the walk found, which the
+    /// bag may no longer hold all of once the known contents are dropped.
+    pub fn of<K>(files: usize, bag: &yadf::FileCounter<K>) -> Self {
+        let mut stats = Self {
+            files,
+            ..Self::default()
+        };
+        for bucket in bag.duplicates().iter() {
+            stats.groups += 1;
+            stats.duplicates += bucket.len();
+            let size = bucket
+                .first()
+                .and_then(yadf::Path::info)
+                .map_or(0, |info| info.size);
+            stats.wasted += size * (bucket.len() as u64 - 1);
+        }
+        stats
+    }
+}
+
+impl Loaded {
+    pub fn read(path: &std::path::Path) -> anyhow::Result<Self> {
+        let context = || format!("reading the report: {:?}", path.display());
+        let file = std::fs::File::open(path).with_context(context)?;
+        serde_json::from_reader(std::io::BufReader::new(file)).with_context(context)
+    }
+
+    /// The algorithm the report was made with, if this build knows it.
+    pub fn algorithm(&self) -> anyhow::Result<Algorithm> {
+        Algorithm::from_str(&self.algorithm, true)
+            .map_err(|_| anyhow::anyhow!("unknown algorithm in the report: {:?}", self.algorithm))
+    }
+
+    /// The groups of the report, keyed by their hex hash.
+    pub fn into_bag(self) -> yadf::TreeBag<String, yadf::Path> {
+        self.groups
+            .into_iter()
+            .flat_map(|group| {
+                let hash = group.hash;
+                group
+                    .files
+                    .into_iter()
+                    .map(move |file| (hash.clone(), file.path))
+            })
+            .collect()
+    }
+}
+
+fn version() -> &'static str {
+    let long_version = env!("YADF_BUILD_VERSION");
+    long_version.split('|').next().unwrap_or(long_version)
+}
+
+fn timestamp(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string()
+}
+
+impl<K: yadf::HashBytes> Serialize for Report<'_, K> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let algorithm = self.args.algorithm.to_possible_value();
+        let roots: Vec<_> = self.args.paths.iter().map(yadf::Path::from).collect();
+        let mut report = serializer.serialize_struct("Report", 9)?;
+        report.serialize_field("yadf", version())?;
+        report.serialize_field("algorithm", &algorithm.as_ref().map(|a| a.get_name()))?;
+        report.serialize_field("roots", &roots)?;
+        report.serialize_field("filters", &Filters(self.args))?;
+        report.serialize_field("started", &timestamp(self.started))?;
+        report.serialize_field("finished", &timestamp(self.finished))?;
+        report.serialize_field("every_file", &self.args.every_file)?;
+        report.serialize_field("stats", &self.stats)?;
+        report.serialize_field("groups", &self.replicates.rich())?;
+        report.end()
+    }
+}
+
+impl Serialize for Stats {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut stats = serializer.serialize_struct("Stats", 4)?;
+        stats.serialize_field("files", &self.files)?;
+        stats.serialize_field("groups", &self.groups)?;
+        stats.serialize_field("duplicates", &self.duplicates)?;
+        stats.serialize_field("wasted", &self.wasted)?;
+        stats.end()
+    }
+}
+
+impl Serialize for Filters<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let args = self.0;
+        let rfactor = args.rfactor.clone().unwrap_or_default();
+        let mut filters = serializer.serialize_struct("Filters", 8)?;
+        filters.serialize_field("min", &args.min())?;
+        filters.serialize_field("max", &args.max())?;
+        filters.serialize_field("depth", &args.max_depth)?;
+        filters.serialize_field("regex", &args.regex.as_ref().map(regex::Regex::as_str))?;
+        filters.serialize_field("pattern", &args.pattern.as_ref().map(globset::Glob::glob))?;
+        #[cfg(unix)]
+        filters.serialize_field("hard_links", &args.hard_links)?;
+        filters.serialize_field("rfactor", &rfactor_name(&rfactor))?;
+        let ignore_known = args.ignore_known.as_ref().map(yadf::Path::from);
+        filters.serialize_field("ignore_known", &ignore_known)?;
+        filters.end()
+    }
+}
+
+/// The replication factor, the way it is written on the command line.
+fn rfactor_name(rfactor: &ReplicationFactor) -> String {
+    match rfactor {
+        ReplicationFactor::Under(n) => format!("under:{n}"),
+        ReplicationFactor::Equal(n) => format!("equal:{n}"),
+        ReplicationFactor::Over(n) => format!("over:{n}"),
+    }
+}
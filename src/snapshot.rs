@@ -0,0 +1,261 @@
+//! `yadf snapshot` and `yadf diff`: every file of a tree hashed in full, and
+//! what changed since, or since a report listing every file.
+
+use super::report::Loaded;
+use super::with_output;
+use anyhow::Context;
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use yadf::Change;
+use yadf::{Algorithm, WithHasher};
+
+pub fn snapshot(
+    paths: &[PathBuf],
+    algorithm: &Algorithm,
+    output: Option<&Path>,
+) -> anyhow::Result<()> {
+    bail_if_unstable(algorithm)?;
+    algorithm.dispatch(Snapshot { paths, output })
+}
+
+pub fn diff(
+    snapshot: &Path,
+    paths: &[PathBuf],
+    algorithm: &Algorithm,
+    save: Option<&Path>,
+    json: bool,
+) -> anyhow::Result<()> {
+    let context = || format!("reading the snapshot: {:?}", snapshot.display());
+    let mut file = io::BufReader::new(std::fs::File::open(snapshot).with_context(context)?);
+    if is_report(&mut file).with_context(context)? {
+        return diff_report(snapshot, paths, save, json);
+    }
+    bail_if_unstable(algorithm)?;
+    let before = yadf::Manifest::read(file).with_context(context)?;
+    algorithm.dispatch(Diff {
+        before,
+        paths,
+        save,
+        report: None,
+        json,
+    })
+}
+
+/// Whether `reader` holds a JSON report rather than checksum lines, which
+/// never start with a `{`.
+fn is_report<R: BufRead>(reader: &mut R) -> io::Result<bool> {
+    loop {
+        let buffer = reader.fill_buf()?;
+        match buffer.iter().position(|byte| !byte.is_ascii_whitespace()) {
+            Some(start) => return Ok(buffer[start] == b'{'),
+            None if buffer.is_empty() => return Ok(false),
+            None => {
+                let len = buffer.len();
+                reader.consume(len);
+            }
+        }
+    }
+}
+
+/// [`diff`] with a report standing in for the snapshot: its files are
+/// compared under the hashes it keyed them by, which are only those of
+/// their whole contents for the larger ones.
+fn diff_report(
+    report: &Path,
+    paths: &[PathBuf],
+    save: Option<&Path>,
+    json: bool,
+) -> anyhow::Result<()> {
+    if save.is_some() {
+        anyhow::bail!("`--save` needs a snapshot to compare with, not a report");
+    }
+    let loaded = Loaded::read(report)?;
+    let algorithm = loaded.algorithm()?;
+    if !algorithm.is_stable() {
+        anyhow::bail!(
+            "the report was made with {:?}, its hashes can't be computed again",
+            algorithm
+        );
+    }
+    if !loaded.every_file {
+        anyhow::bail!(
+            "{:?} only lists duplicates, the files without one would all show as added: \
+            make it with `--every-file`",
+            report.display()
+        );
+    }
+    let before = yadf::Manifest::from_hex(loaded.groups.iter().flat_map(|group| {
+        group
+            .files
+            .iter()
+            .map(|file| (group.hash.as_str(), file.path.as_ref().to_path_buf()))
+    }))
+    .ok_or_else(|| anyhow::anyhow!("{:?} holds a hash which isn't hex", report.display()))?;
+    algorithm.dispatch(Diff {
+        before,
+        paths,
+        save,
+        report: Some(FromReport {
+            hard_links: loaded.filters.hard_links,
+        }),
+        json,
+    })
+}
+
+fn bail_if_unstable(algorithm: &Algorithm) -> anyhow::Result<()> {
+    if !algorithm.is_stable() {
+        anyhow::bail!(
+            "{:?} hashes change from one run to the next, \
+            they can't be compared with a snapshot",
+            algorithm
+        );
+    }
+    Ok(())
+}
+
+struct Snapshot<'a> {
+    paths: &'a [PathBuf],
+    output: Option<&'a Path>,
+}
+
+struct Diff<'a> {
+    before: yadf::Manifest,
+    paths: &'a [PathBuf],
+    save: Option<&'a Path>,
+    /// The report `before` comes from, if it isn't a snapshot.
+    report: Option<FromReport>,
+    json: bool,
+}
+
+/// How a report listed the files, to list them the same way again.
+struct FromReport {
+    hard_links: bool,
+}
+
+/// Every file under `paths`, hard links included: a second link appearing
+/// or disappearing is a change like any other.
+fn take<H>(paths: &[PathBuf]) -> yadf::Manifest
+where
+    H: yadf::Hasher,
+    H::Hash: yadf::HashBytes,
+{
+    let config = yadf::Yadf::builder().paths(paths);
+    #[cfg(unix)]
+    let config = config.hard_links(true);
+    config.build().snapshot::<H>()
+}
+
+/// [`take`], under the hashes a report keys its files by, with hard links
+/// listed only if the report listed them.
+fn take_group_keys<H>(paths: &[PathBuf], hard_links: bool) -> yadf::Manifest
+where
+    H: yadf::Hasher,
+    H::Hash: yadf::HashBytes,
+{
+    let config = yadf::Yadf::builder().paths(paths);
+    #[cfg(unix)]
+    let config = config.hard_links(hard_links);
+    #[cfg(not(unix))]
+    let _ = hard_links;
+    config.build().group_key_snapshot::<H>()
+}
+
+impl WithHasher for Snapshot<'_> {
+    type Output = anyhow::Result<()>;
+
+    fn run<H>(self) -> anyhow::Result<()>
+    where
+        H: yadf::Hasher,
+        H::Hash: std::fmt::Debug + yadf::HashBytes,
+    {
+        let manifest = take::<H>(self.paths);
+        with_output(self.output, |writer| {
+            let mut writer = io::BufWriter::new(writer);
+            manifest.write_to(&mut writer)?;
+            writer.flush()?;
+            Ok(())
+        })
+    }
+}
+
+impl WithHasher for Diff<'_> {
+    type Output = anyhow::Result<()>;
+
+    fn run<H>(self) -> anyhow::Result<()>
+    where
+        H: yadf::Hasher,
+        H::Hash: std::fmt::Debug + yadf::HashBytes,
+    {
+        let after = if let Some(report) = self.report {
+            take_group_keys::<H>(self.paths, report.hard_links)
+        } else {
+            take::<H>(self.paths)
+        };
+        if let Some(path) = self.save {
+            super::write_manifest(path, &after)?;
+        }
+        let changes = self.before.diff(&after);
+        let mut stdout = io::BufWriter::new(io::stdout().lock());
+        if self.json {
+            let changes: Vec<_> = changes.iter().map(Json).collect();
+            serde_json::to_writer(&mut stdout, &changes)?;
+            writeln!(stdout)?;
+        } else {
+            for change in &changes {
+                match change {
+                    Change::Added(path) => writeln!(stdout, "added {}", path.display())?,
+                    Change::Deleted(path) => writeln!(stdout, "deleted {}", path.display())?,
+                    Change::Modified(path) => writeln!(stdout, "modified {}", path.display())?,
+                    Change::Moved { from, to } => {
+                        writeln!(stdout, "moved {} -> {}", from.display(), to.display())?
+                    }
+                    Change::Copied { from, to } => {
+                        writeln!(stdout, "copied {} -> {}", from.display(), to.display())?
+                    }
+                }
+            }
+        }
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+/// A [`Change`] as `{"change": "moved", "from": ..., "to": ...}`, with `path`
+/// instead of `from`/`to` for changes to a single path.
+struct Json<'a>(&'a Change<'a>);
+
+impl Serialize for Json<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self.0 {
+            Change::Added(path) => single(serializer, "added", path),
+            Change::Deleted(path) => single(serializer, "deleted", path),
+            Change::Modified(path) => single(serializer, "modified", path),
+            Change::Moved { from, to } => pair(serializer, "moved", from, to),
+            Change::Copied { from, to } => pair(serializer, "copied", from, to),
+        }
+    }
+}
+
+fn single<S: Serializer>(serializer: S, kind: &str, path: &Path) -> Result<S::Ok, S::Error> {
+    let mut change = serializer.serialize_struct("Change", 2)?;
+    change.serialize_field("change", kind)?;
+    change.serialize_field("path", &yadf::Path::from(path))?;
+    change.end()
+}
+
+fn pair<S: Serializer>(
+    serializer: S,
+    kind: &str,
+    from: &Path,
+    to: &Path,
+) -> Result<S::Ok, S::Error> {
+    let mut change = serializer.serialize_struct("Change", 3)?;
+    change.serialize_field("change", kind)?;
+    change.serialize_field("from", &yadf::Path::from(from))?;
+    change.serialize_field("to", &yadf::Path::from(to))?;
+    change.end()
+}
@@ -0,0 +1,212 @@
+//! `yadf verify`: an earlier report checked against the files as they are
+//! now, so it can be acted upon without trusting it blindly.
+
+use super::report::Loaded;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use yadf::WithHasher;
+
+pub fn run(report: &Path, json: bool) -> anyhow::Result<()> {
+    let loaded = Loaded::read(report)?;
+    let algorithm = loaded.algorithm()?;
+    if !algorithm.is_stable() {
+        anyhow::bail!(
+            "the report was made with {:?}, its hashes can't be computed again",
+            algorithm
+        );
+    }
+    algorithm.dispatch(Verify {
+        groups: loaded.into_bag(),
+        json,
+    })
+}
+
+struct Verify {
+    groups: yadf::TreeBag<String, yadf::Path>,
+    json: bool,
+}
+
+/// How the groups of the report fared.
+#[derive(Debug, Default, Serialize)]
+struct Outcome<'a> {
+    /// Groups whose remaining files all still share the reported content.
+    holds: Vec<Held<'a>>,
+    /// Groups whose files don't all share the reported content any more, or
+    /// with fewer than two files left of several.
+    split: Vec<Split<'a>>,
+    /// Files listed in the report which are gone.
+    disappeared: Vec<&'a yadf::Path>,
+    /// Files listed in the report which are still there, but couldn't be
+    /// read.
+    unreadable: Vec<&'a yadf::Path>,
+}
+
+#[derive(Debug, Serialize)]
+struct Held<'a> {
+    hash: &'a str,
+    files: Vec<&'a yadf::Path>,
+}
+
+#[derive(Debug, Serialize)]
+struct Split<'a> {
+    hash: &'a str,
+    /// The files sharing the same content now.
+    groups: Vec<Vec<&'a yadf::Path>>,
+}
+
+impl WithHasher for Verify {
+    type Output = anyhow::Result<()>;
+
+    fn run<H>(self) -> anyhow::Result<()>
+    where
+        H: yadf::Hasher,
+        H::Hash: std::fmt::Debug + yadf::HashBytes,
+    {
+        let present: Vec<PathBuf> = self
+            .groups
+            .as_inner()
+            .values()
+            .flatten()
+            .map(|path| path.as_ref().to_path_buf())
+            .filter(|path| path.is_file())
+            .collect();
+        let mut current = rehash::<H>(present.clone());
+        for path in present {
+            current.entry(path).or_default();
+        }
+        let outcome = compare(&self.groups, &current);
+        let mut stdout = io::BufWriter::new(io::stdout().lock());
+        if self.json {
+            serde_json::to_writer(&mut stdout, &outcome)?;
+            writeln!(stdout)?;
+        } else {
+            outcome.write_text(&mut stdout)?;
+        }
+        Ok(())
+    }
+}
+
+/// The hex hash of each of `files`, the one its group in a report would be
+/// keyed by, so it can be compared with the report's. The files which
+/// couldn't be read are left out.
+fn rehash<H>(files: Vec<PathBuf>) -> HashMap<PathBuf, Option<String>>
+where
+    H: yadf::Hasher,
+    H::Hash: std::fmt::Debug + yadf::HashBytes,
+{
+    let bag = yadf::Yadf::builder().paths(files).build().group_keys::<H>();
+    bag.as_inner()
+        .iter()
+        .flat_map(|(hash, bucket)| {
+            let hash = yadf::Hex(*hash).to_string();
+            bucket
+                .iter()
+                .map(move |path| (path.as_ref().to_path_buf(), Some(hash.clone())))
+        })
+        .collect()
+}
+
+/// Sorts the groups of the report by how they fared, `current` holding the
+/// hash of every file still there, `None` if it couldn't be read.
+fn compare<'a>(
+    groups: &'a yadf::TreeBag<String, yadf::Path>,
+    current: &HashMap<PathBuf, Option<String>>,
+) -> Outcome<'a> {
+    let mut outcome = Outcome::default();
+    for (hash, files) in groups.as_inner() {
+        let mut now: BTreeMap<&str, Vec<&yadf::Path>> = BTreeMap::new();
+        for file in files {
+            match current.get(file.as_ref()) {
+                Some(Some(new_hash)) => now.entry(new_hash).or_default().push(file),
+                Some(None) => outcome.unreadable.push(file),
+                None => outcome.disappeared.push(file),
+            }
+        }
+        // A report listing every file has groups of one, which hold as long
+        // as their file is unchanged.
+        let alone = files.len() == 1;
+        match now.remove(hash.as_str()) {
+            Some(files) if now.is_empty() && (files.len() > 1 || alone) => {
+                outcome.holds.push(Held { hash, files })
+            }
+            same => outcome.split.push(Split {
+                hash,
+                groups: same.into_iter().chain(now.into_values()).collect(),
+            }),
+        }
+    }
+    outcome
+}
+
+impl Outcome<'_> {
+    /// One block per group, `holds` or `split` followed by its hash and its
+    /// files, the parts of a split separated by `--`. Blocks are separated by
+    /// blank lines, like fdupes' output.
+    fn write_text<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let holds = self.holds.iter().map(|held| {
+            let parts = std::slice::from_ref(&held.files);
+            (format!("holds {}", held.hash), parts)
+        });
+        let split = self.split.iter().map(|split| {
+            let parts = split.groups.as_slice();
+            (format!("split {}", split.hash), parts)
+        });
+        let disappeared = (!self.disappeared.is_empty()).then(|| {
+            let parts = std::slice::from_ref(&self.disappeared);
+            ("disappeared".to_string(), parts)
+        });
+        let unreadable = (!self.unreadable.is_empty()).then(|| {
+            let parts = std::slice::from_ref(&self.unreadable);
+            ("unreadable".to_string(), parts)
+        });
+        let blocks = holds.chain(split).chain(disappeared).chain(unreadable);
+        for (i, (title, parts)) in blocks.enumerate() {
+            if i > 0 {
+                writeln!(writer)?;
+            }
+            writeln!(writer, "{title}")?;
+            for (j, part) in parts.iter().enumerate() {
+                if j > 0 {
+                    writeln!(writer, "--")?;
+                }
+                for path in part {
+                    writeln!(writer, "{}", path.as_ref().display())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_still_there_but_unreadable_have_not_disappeared() {
+        let groups: yadf::TreeBag<String, yadf::Path> =
+            [("aa", "a"), ("aa", "b"), ("aa", "gone"), ("bb", "alone")]
+                .into_iter()
+                .map(|(hash, path)| (hash.to_owned(), yadf::Path::from(path)))
+                .collect();
+        let current: HashMap<_, _> = [("a", Some("aa")), ("b", None), ("alone", Some("bb"))]
+            .into_iter()
+            .map(|(path, hash)| (PathBuf::from(path), hash.map(str::to_owned)))
+            .collect();
+        let outcome = compare(&groups, &current);
+        let paths = |files: &[&yadf::Path]| -> Vec<PathBuf> {
+            files
+                .iter()
+                .map(|path| path.as_ref().to_path_buf())
+                .collect()
+        };
+        assert_eq!(paths(&outcome.unreadable), [PathBuf::from("b")]);
+        assert_eq!(paths(&outcome.disappeared), [PathBuf::from("gone")]);
+        assert_eq!(outcome.holds.len(), 1);
+        assert_eq!(outcome.holds[0].hash, "bb");
+        assert_eq!(outcome.split.len(), 1);
+        assert_eq!(outcome.split[0].hash, "aa");
+    }
+}
@@ -0,0 +1,396 @@
+//! Where a scan finds its files: the operating system's filesystem by
+//! default, or anything able to list files with their sizes and to read
+//! them, such as an archive, a remote store, or a tree built in memory.
+//!
+//! # Example
+//!
+//! ```
+//! use yadf::vfs::MemoryFileSystem;
+//!
+//! let mut files = MemoryFileSystem::default();
+//! files.insert("/tree/a", "same");
+//! files.insert("/tree/b", "same");
+//! files.insert("/tree/c", "other");
+//! let counter = yadf::Yadf::builder()
+//!     .paths(["/tree"])
+//!     .filesystem(files)
+//!     .build()
+//!     .scan::<seahash::SeaHasher>();
+//! assert_eq!(counter.duplicates().iter().count(), 1);
+//! ```
+
+pub use crate::fs::os::Os;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A source of files to scan.
+///
+/// Only [`walk`](FileSystem::walk), [`metadata`](FileSystem::metadata) and
+/// [`open`](FileSystem::open) are required; the other methods have defaults
+/// fit for anything but the operating system's filesystem.
+pub trait FileSystem: fmt::Debug + Send + Sync {
+    /// Calls `visit` with every entry under `roots`, the roots included,
+    /// descending at most `max_depth` levels below each, and going on as
+    /// `visit` says.
+    ///
+    /// `visit` may be called from several threads at once, and in any order
+    /// but one: a directory comes before its contents, which are left out if
+    /// it is skipped. Entries which aren't files are left out by the scan
+    /// itself.
+    fn walk(
+        &self,
+        roots: &[&Path],
+        max_depth: Option<usize>,
+        visit: &(dyn Fn(io::Result<Entry>) -> Visit + Sync),
+    );
+
+    /// The metadata of the file at `path`.
+    fn metadata(&self, path: &Path) -> io::Result<Metadata>;
+
+    /// The metadata of the entry at `path` itself, a link rather than what
+    /// it points to, the way a walk sees it.
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+        self.metadata(path)
+    }
+
+    /// Opens the file at `path` for reading, the way `access` says.
+    fn open(&self, path: &Path, access: Access) -> io::Result<Box<dyn FileReader + '_>>;
+
+    /// The one path all the ways of naming a file resolve to, to tell
+    /// whether two paths are the same file.
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(path.to_path_buf())
+    }
+
+    /// Whether [`prefetch`](FileSystem::prefetch) does anything, sparing the
+    /// threads which would call it otherwise.
+    fn prefetches(&self) -> bool {
+        false
+    }
+
+    /// Starts loading the first `len` bytes of `path`, which are about to be
+    /// read, without waiting for them. Failures must go unnoticed.
+    fn prefetch(&self, _path: &Path, _len: u64) {}
+
+    /// Whether [`read_batch`](FileSystem::read_batch) does better than
+    /// opening and reading each file in turn, in which case the scan hands
+    /// it whole batches of reads and doesn't prefetch them.
+    fn batches_reads(&self) -> bool {
+        false
+    }
+
+    /// Reads every block of `reads`, calling `done` with its index in
+    /// `reads` and its bytes, in any order but exactly once each.
+    fn read_batch(&self, reads: &[BlockRead<'_>], done: &mut dyn FnMut(usize, io::Result<&[u8]>)) {
+        read_each(self, reads, done);
+    }
+}
+
+/// [`FileSystem::read_batch`] one file after the other, each opened and
+/// read on its own.
+pub(crate) fn read_each<F>(
+    fs: &F,
+    reads: &[BlockRead<'_>],
+    done: &mut dyn FnMut(usize, io::Result<&[u8]>),
+) where
+    F: FileSystem + ?Sized,
+{
+    let mut buffer = Vec::new();
+    for (index, read) in reads.iter().enumerate() {
+        buffer.resize(read.len, 0);
+        let result = fs
+            .open(read.path, Access::Random)
+            .and_then(|mut file| file.read_exact_at(&mut buffer, read.offset));
+        done(index, result.map(|()| &buffer[..]));
+    }
+}
+
+/// `len` bytes from `offset` in the file at `path`, for
+/// [`FileSystem::read_batch`]. Reaching the end of the file first is an
+/// error.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockRead<'a> {
+    pub path: &'a Path,
+    pub offset: u64,
+    pub len: usize,
+}
+
+/// A file opened by a [`FileSystem`].
+pub trait FileReader {
+    /// Reads from where the last read stopped, like [`io::Read::read`].
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize>;
+
+    /// Fills `buffer` from `offset`, like
+    /// [`FileExt::read_exact_at`](std::os::unix::fs::FileExt::read_exact_at).
+    fn read_exact_at(&mut self, buffer: &mut [u8], offset: u64) -> io::Result<()>;
+}
+
+/// How a file is about to be read. A hint, for the filesystems which can
+/// make use of one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// A short read of one region and nothing else.
+    Random,
+    /// A front-to-back read of the whole file.
+    Sequential,
+}
+
+/// What [`FileSystem::walk`] does after an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visit {
+    /// Goes on, into the entry if it is a directory.
+    Continue,
+    /// Goes on, but not into the entry.
+    Skip,
+    /// Stops the walk.
+    Quit,
+}
+
+/// An entry found by [`FileSystem::walk`].
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub metadata: Metadata,
+}
+
+/// What the scan needs to know about an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Metadata {
+    /// Whether it is a regular file, as opposed to a directory or a link.
+    pub is_file: bool,
+    /// Whether it is a directory, which a walk may descend into.
+    pub is_dir: bool,
+    pub len: u64,
+    /// Last modification, in seconds since the Unix epoch.
+    pub modified: Option<i64>,
+    /// `(dev, ino)`, or whatever tells hard links to the same file apart
+    /// from copies of it.
+    pub id: Option<(u64, u64)>,
+    /// Number of hard links to the file.
+    pub nlink: Option<u64>,
+}
+
+/// Files held in memory, for tests and for contents which were never files.
+///
+/// Directories are implied by the paths of the files.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryFileSystem {
+    files: BTreeMap<PathBuf, Arc<[u8]>>,
+}
+
+impl MemoryFileSystem {
+    /// Adds a file, or replaces one.
+    pub fn insert(&mut self, path: impl Into<PathBuf>, contents: impl AsRef<[u8]>) {
+        self.files.insert(path.into(), contents.as_ref().into());
+    }
+
+    /// Removes a file, returning whether it was there.
+    pub fn remove(&mut self, path: impl AsRef<Path>) -> bool {
+        self.files.remove(path.as_ref()).is_some()
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn walk(
+        &self,
+        roots: &[&Path],
+        max_depth: Option<usize>,
+        visit: &(dyn Fn(io::Result<Entry>) -> Visit + Sync),
+    ) {
+        let too_deep = |below: &Path| max_depth.is_some_and(|max| below.components().count() > max);
+        for root in roots {
+            let mut found = false;
+            let mut visited = HashSet::new();
+            let mut skipped: Option<&Path> = None;
+            'files: for (path, contents) in self.files.range(root.to_path_buf()..) {
+                let Ok(below) = path.strip_prefix(root) else {
+                    break;
+                };
+                found = true;
+                if skipped.is_some_and(|skipped| path.starts_with(skipped)) {
+                    continue;
+                }
+                // The implied directories on the way, the root first.
+                let mut directories: Vec<&Path> = path
+                    .ancestors()
+                    .skip(1)
+                    .take_while(|directory| directory.starts_with(root))
+                    .collect();
+                while let Some(directory) = directories.pop() {
+                    let below = directory.strip_prefix(root).unwrap_or(directory);
+                    if too_deep(below) || !visited.insert(directory) {
+                        continue;
+                    }
+                    let entry = Entry {
+                        path: directory.to_path_buf(),
+                        metadata: DIRECTORY,
+                    };
+                    match visit(Ok(entry)) {
+                        Visit::Continue => {}
+                        Visit::Skip => {
+                            skipped = Some(directory);
+                            continue 'files;
+                        }
+                        Visit::Quit => return,
+                    }
+                }
+                if too_deep(below) {
+                    continue;
+                }
+                let entry = Entry {
+                    path: path.clone(),
+                    metadata: file_metadata(contents),
+                };
+                if visit(Ok(entry)) == Visit::Quit {
+                    return;
+                }
+            }
+            if !found {
+                let error = io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{}: no such file or directory", root.display()),
+                );
+                if visit(Err(error)) == Visit::Quit {
+                    return;
+                }
+            }
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+        match self.files.get(path) {
+            Some(contents) => Ok(file_metadata(contents)),
+            None if self.files.keys().any(|file| file.starts_with(path)) => Ok(DIRECTORY),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn open(&self, path: &Path, _access: Access) -> io::Result<Box<dyn FileReader + '_>> {
+        let contents = self.files.get(path).ok_or(io::ErrorKind::NotFound)?;
+        Ok(Box::new(io::Cursor::new(&contents[..])))
+    }
+}
+
+const DIRECTORY: Metadata = Metadata {
+    is_file: false,
+    is_dir: true,
+    len: 0,
+    modified: None,
+    id: None,
+    nlink: None,
+};
+
+fn file_metadata(contents: &[u8]) -> Metadata {
+    Metadata {
+        is_file: true,
+        len: contents.len() as u64,
+        ..Metadata::default()
+    }
+}
+
+impl FileReader for io::Cursor<&[u8]> {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        io::Read::read(self, buffer)
+    }
+
+    fn read_exact_at(&mut self, buffer: &mut [u8], offset: u64) -> io::Result<()> {
+        self.set_position(offset);
+        io::Read::read_exact(self, buffer)
+    }
+}
+
+impl From<&std::fs::Metadata> for Metadata {
+    fn from(meta: &std::fs::Metadata) -> Self {
+        let modified =
+            meta.modified()
+                .ok()
+                .map(|time| match time.duration_since(std::time::UNIX_EPOCH) {
+                    Ok(since) => since.as_secs() as i64,
+                    Err(before) => -(before.duration().as_secs() as i64),
+                });
+        #[cfg(unix)]
+        let (id, nlink) = {
+            use std::os::unix::fs::MetadataExt;
+            (Some((meta.dev(), meta.ino())), Some(meta.nlink()))
+        };
+        #[cfg(not(unix))]
+        let (id, nlink) = (None, None);
+        Self {
+            is_file: meta.is_file(),
+            is_dir: meta.is_dir(),
+            len: meta.len(),
+            modified,
+            id,
+            nlink,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walk(files: &MemoryFileSystem, roots: &[&str], depth: Option<usize>) -> Vec<String> {
+        let roots: Vec<&Path> = roots.iter().map(Path::new).collect();
+        let found = std::sync::Mutex::new(Vec::new());
+        files.walk(&roots, depth, &|entry| {
+            let entry = match entry {
+                Ok(entry) if entry.metadata.is_dir => format!("{}/", entry.path.display()),
+                Ok(entry) => entry.path.display().to_string(),
+                Err(error) => format!("{:?}", error.kind()),
+            };
+            let visit = if entry == "/a/b/" {
+                Visit::Skip
+            } else {
+                Visit::Continue
+            };
+            found.lock().unwrap().push(entry);
+            visit
+        });
+        found.into_inner().unwrap()
+    }
+
+    #[test]
+    fn memory_walk_follows_roots_and_depth() {
+        let mut files = MemoryFileSystem::default();
+        files.insert("/a/1", "x");
+        files.insert("/a/c/2", "yy");
+        files.insert("/ab", "z");
+        assert_eq!(
+            walk(&files, &["/a"], None),
+            ["/a/", "/a/1", "/a/c/", "/a/c/2"]
+        );
+        assert_eq!(walk(&files, &["/a"], Some(1)), ["/a/", "/a/1", "/a/c/"]);
+        assert_eq!(walk(&files, &["/ab"], None), ["/ab"]);
+        assert_eq!(walk(&files, &["/c"], None), ["NotFound"]);
+    }
+
+    #[test]
+    fn memory_walk_skips_directories() {
+        let mut files = MemoryFileSystem::default();
+        files.insert("/a/1", "x");
+        files.insert("/a/b/2", "yy");
+        files.insert("/a/b/c/3", "z");
+        files.insert("/a/d/4", "z");
+        assert_eq!(
+            walk(&files, &["/a"], None),
+            ["/a/", "/a/1", "/a/b/", "/a/d/", "/a/d/4"]
+        );
+    }
+
+    #[test]
+    fn memory_reads() {
+        let mut files = MemoryFileSystem::default();
+        files.insert("/f", "hello world");
+        let mut reader = files.open(Path::new("/f"), Access::Random).unwrap();
+        let mut buffer = [0; 5];
+        reader.read_exact_at(&mut buffer, 6).unwrap();
+        assert_eq!(&buffer, b"world");
+        assert_eq!(files.metadata(Path::new("/f")).unwrap().len, 11);
+        assert!(files.metadata(Path::new("/")).unwrap().is_dir);
+        assert!(files.open(Path::new("/g"), Access::Random).is_err());
+    }
+}
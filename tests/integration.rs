@@ -180,3 +180,859 @@ fn hard_links_flag() -> AnyResult {
         .stdout(predicate);
     Ok(())
 }
+
+#[test]
+fn directories() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(4096);
+    for copy in ["original", "copy"] {
+        std::fs::create_dir_all(root.as_ref().join(copy).join("nested"))?;
+        root.write_file(format!("{copy}/file"), &bytes)?;
+        root.write_file(format!("{copy}/nested/file"), &bytes[..1024])?;
+    }
+    std::fs::create_dir(root.as_ref().join("partial"))?;
+    root.write_file("partial/file", &bytes)?;
+    let expected = [root.as_ref().join("copy"), root.as_ref().join("original")]
+        .map(|path| path.to_string_lossy().into_owned())
+        .join("\n")
+        + "\n";
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .arg("--directories")
+        .arg(root.as_ref())
+        .assert()
+        .success()
+        .stdout(expected)
+        .stderr(predstr::is_empty());
+    Ok(())
+}
+
+#[test]
+fn similar_directories() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(4096);
+    for dir in ["old/docs", "new/moved"] {
+        std::fs::create_dir_all(root.as_ref().join(dir))?;
+    }
+    root.write_file("old/a", &bytes[..1024])?;
+    root.write_file("old/docs/b", &bytes[..2048])?;
+    root.write_file("new/a", &bytes[..1024])?;
+    root.write_file("new/moved/b", &bytes[..2048])?;
+    root.write_file("new/c", &bytes)?;
+    let expected = format!(
+        "subset 0.667 {} {}\n",
+        root.as_ref().join("old").display(),
+        root.as_ref().join("new").display(),
+    );
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--similar", "1"])
+        .arg(root.as_ref())
+        .assert()
+        .success()
+        .stdout(expected)
+        .stderr(predstr::is_empty());
+    Ok(())
+}
+
+#[test]
+fn missing_from() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(4096);
+    for dir in ["source/nested", "backup/elsewhere"] {
+        std::fs::create_dir_all(root.as_ref().join(dir))?;
+    }
+    root.write_file("source/kept", &bytes[..1024])?;
+    root.write_file("source/nested/moved", &bytes[..2048])?;
+    let changed = root.write_file("source/changed", &bytes[1024..2048])?;
+    let new = root.write_file("source/new", &bytes)?;
+    root.write_file("backup/kept", &bytes[..1024])?;
+    root.write_file("backup/elsewhere/renamed", &bytes[..2048])?;
+    root.write_file("backup/changed", &bytes[2048..3072])?;
+    let expected = format!("{}\n{}\n", changed.display(), new.display());
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .arg(root.as_ref().join("source"))
+        .arg("--missing-from")
+        .arg(root.as_ref().join("backup"))
+        .assert()
+        .success()
+        .stdout(expected)
+        .stderr(predstr::is_empty());
+    Ok(())
+}
+
+#[test]
+fn find_copies_of() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(4096);
+    std::fs::create_dir_all(root.as_ref().join("tree/deep"))?;
+    let query = root.write_file("query", &bytes)?;
+    let copy = root.write_file("tree/deep/copy", &bytes)?;
+    root.write_file("tree/same_size", [&bytes[..4095], b"x"].concat())?;
+    root.write_file("tree/other_a", &bytes[..1024])?;
+    root.write_file("tree/other_b", &bytes[..1024])?;
+    let expected = format!("{}\n{}\n", query.display(), copy.display());
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .arg("--find-copies-of")
+        .arg(&query)
+        .arg(root.as_ref())
+        .assert()
+        .success()
+        .stdout(expected)
+        .stderr(predstr::is_empty());
+    Ok(())
+}
+
+fn seahash_hex(bytes: &[u8]) -> String {
+    use std::hash::Hasher;
+    let mut hasher = seahash::SeaHasher::default();
+    hasher.write(bytes);
+    format!("{:016x}", hasher.finish())
+}
+
+#[test]
+fn known() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(8192);
+    std::fs::create_dir(root.as_ref().join("tree"))?;
+    let leaked = root.write_file("tree/leaked", &bytes)?;
+    root.write_file("tree/other", &bytes[..4096])?;
+    root.write_file("tree/another", &bytes[..4096])?;
+    let list = root.write_file("list", format!("{}  leaked\n", seahash_hex(&bytes)))?;
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--algorithm", "seahash"])
+        .arg("--known")
+        .arg(&list)
+        .arg(root.as_ref().join("tree"))
+        .assert()
+        .success()
+        .stdout(format!("{}\n", leaked.display()))
+        .stderr(predstr::is_empty());
+    Ok(())
+}
+
+#[test]
+fn ignore_known() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(8192);
+    std::fs::create_dir(root.as_ref().join("tree"))?;
+    root.write_file("tree/license_a", &bytes[..100])?;
+    root.write_file("tree/license_b", &bytes[..100])?;
+    let dupe_a = root.write_file("tree/dupe_a", &bytes)?;
+    let dupe_b = root.write_file("tree/dupe_b", &bytes)?;
+    let list = root.write_file("list", seahash_hex(&bytes[..100]))?;
+    let output = assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--algorithm", "seahash", "--format", "machine"])
+        .arg("--ignore-known")
+        .arg(&list)
+        .arg(root.as_ref().join("tree"))
+        .assert()
+        .success()
+        .stderr(predstr::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let output = String::from_utf8(output)?;
+    assert_eq!(output.lines().count(), 1, "{output}");
+    assert!(output.contains(&*dupe_a.to_string_lossy()));
+    assert!(output.contains(&*dupe_b.to_string_lossy()));
+    // Copies of a known content are left out of a search for copies too.
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--algorithm", "seahash", "--format", "machine"])
+        .arg("--ignore-known")
+        .arg(&list)
+        .arg("--find-copies-of")
+        .arg(root.as_ref().join("tree/license_a"))
+        .arg(root.as_ref().join("tree"))
+        .assert()
+        .success()
+        .stdout("\n")
+        .stderr(predstr::is_empty());
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .arg("--ignore-known")
+        .arg(&list)
+        .arg("--known")
+        .arg(&list)
+        .assert()
+        .failure()
+        .stderr(predstr::contains("cannot be used with"));
+    Ok(())
+}
+
+#[test]
+fn with_hashes() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(8192);
+    root.write_file("a", &bytes)?;
+    root.write_file("b", &bytes)?;
+    let output = assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args([
+            "--algorithm",
+            "seahash",
+            "--format",
+            "json",
+            "--with-hashes",
+        ])
+        .arg(root.as_ref())
+        .assert()
+        .success()
+        .stderr(predstr::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let groups: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(groups[0]["hash"], seahash_hex(&bytes));
+    assert_eq!(groups[0]["files"].as_array().map(Vec::len), Some(2));
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--algorithm", "ahash", "--with-hashes"])
+        .arg(root.as_ref())
+        .assert()
+        .failure()
+        .stderr(predstr::contains("can't identify a group"));
+    Ok(())
+}
+
+#[test]
+fn json_rich() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(8192);
+    root.write_file("a", &bytes)?;
+    root.write_file("b", &bytes)?;
+    let output = assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--format", "json-rich"])
+        .arg(root.as_ref())
+        .assert()
+        .success()
+        .stderr(predstr::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let groups: serde_json::Value = serde_json::from_slice(&output)?;
+    let group = &groups[0];
+    assert_eq!(group["size"], 8192);
+    assert_eq!(group["wasted"], 8192);
+    for file in group["files"].as_array().into_iter().flatten() {
+        assert_eq!(file["size"], 8192);
+        assert_eq!(file["root"], 0);
+        assert!(file["mtime"].is_i64());
+        #[cfg(unix)]
+        assert_eq!(file["nlink"], 1);
+    }
+    Ok(())
+}
+
+#[test]
+fn report() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(8192);
+    root.write_file("a", &bytes)?;
+    root.write_file("b", &bytes)?;
+    root.write_file("c", &bytes[..100])?;
+    let output = assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args([
+            "--format",
+            "report",
+            "--algorithm",
+            "seahash",
+            "--min",
+            "10",
+        ])
+        .arg(root.as_ref())
+        .assert()
+        .success()
+        .stderr(predstr::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let report: serde_json::Value = serde_json::from_slice(&output)?;
+    assert!(report["yadf"]
+        .as_str()
+        .is_some_and(|v| v.starts_with(env!("CARGO_PKG_VERSION"))));
+    assert_eq!(report["algorithm"], "seahash");
+    assert_eq!(report["roots"][0], root.as_ref().to_string_lossy().as_ref());
+    assert_eq!(report["filters"]["min"], 10);
+    assert_eq!(report["filters"]["rfactor"], "over:1");
+    assert!(report["started"].as_str() <= report["finished"].as_str());
+    assert_eq!(report["stats"]["files"], 3);
+    assert_eq!(report["stats"]["groups"], 1);
+    assert_eq!(report["stats"]["wasted"], 8192);
+    assert_eq!(report["groups"][0]["hash"], seahash_hex(&bytes));
+    // Dropping the known groups leaves the count of files found as it is.
+    let list = root.write_file("list", seahash_hex(&bytes))?;
+    let output = assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--format", "report", "--algorithm", "seahash"])
+        .arg("--ignore-known")
+        .arg(&list)
+        .arg(root.as_ref())
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    let report: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(report["stats"]["files"], 4);
+    assert_eq!(report["stats"]["groups"], 0);
+    Ok(())
+}
+
+#[test]
+fn verify() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(8192);
+    std::fs::create_dir(root.as_ref().join("tree"))?;
+    let a = root.write_file("tree/a", &bytes)?;
+    let b = root.write_file("tree/b", &bytes)?;
+    let c = root.write_file("tree/c", &bytes)?;
+    let x = root.write_file("tree/x", &bytes[..100])?;
+    let y = root.write_file("tree/y", &bytes[..100])?;
+    let report = root.as_ref().join("report.json");
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--format", "report", "--output"])
+        .arg(&report)
+        .arg(root.as_ref().join("tree"))
+        .assert()
+        .success();
+    root.write_file("tree/c", &bytes[..200])?;
+    std::fs::remove_file(&y)?;
+    let output = assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["verify", "--json"])
+        .arg(&report)
+        .assert()
+        .success()
+        .stderr(predstr::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let outcome: serde_json::Value = serde_json::from_slice(&output)?;
+    let path = |path: &std::path::Path| serde_json::Value::from(path.to_string_lossy());
+    assert!(outcome["holds"].as_array().is_some_and(Vec::is_empty));
+    let mut split = outcome["split"].as_array().cloned().unwrap_or_default();
+    split.sort_by_key(|split| split["groups"].as_array().map(Vec::len));
+    assert_eq!(split[0]["groups"], serde_json::json!([[path(&x)]]));
+    let mut parts = split[1]["groups"].as_array().cloned().unwrap_or_default();
+    parts.sort_by_key(|part| part.as_array().map(Vec::len));
+    assert_eq!(parts[0], serde_json::json!([path(&c)]));
+    let mut held: Vec<_> = parts[1].as_array().cloned().unwrap_or_default();
+    held.sort_by_key(|p| p.as_str().map(str::to_owned));
+    assert_eq!(held, [path(&a), path(&b)]);
+    assert_eq!(outcome["disappeared"], serde_json::json!([path(&y)]));
+    Ok(())
+}
+
+#[test]
+fn verify_puts_the_files_left_unchanged_first() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(8192 * 3);
+    std::fs::create_dir(root.as_ref().join("tree"))?;
+    let a = root.write_file("tree/a", &bytes[..8192])?;
+    root.write_file("tree/b", &bytes[..8192])?;
+    root.write_file("tree/c", &bytes[..8192])?;
+    let report = root.as_ref().join("report.json");
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--format", "report", "--output"])
+        .arg(&report)
+        .arg(root.as_ref().join("tree"))
+        .assert()
+        .success();
+    // Alone of its content now, `a` is still known by the reported hash.
+    root.write_file("tree/b", &bytes[8192..16384])?;
+    root.write_file("tree/c", &bytes[16384..])?;
+    let output = assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["verify", "--json"])
+        .arg(&report)
+        .assert()
+        .success()
+        .stderr(predstr::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let outcome: serde_json::Value = serde_json::from_slice(&output)?;
+    let groups = &outcome["split"][0]["groups"];
+    assert_eq!(groups.as_array().map(Vec::len), Some(3));
+    assert_eq!(groups[0], serde_json::json!([a.to_string_lossy()]));
+    Ok(())
+}
+
+#[test]
+fn verify_holds_the_single_files_of_every_file_reports() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(8192);
+    std::fs::create_dir(root.as_ref().join("tree"))?;
+    let alone = root.write_file("tree/alone", &bytes)?;
+    let report = root.as_ref().join("report.json");
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--format", "report", "--every-file", "--output"])
+        .arg(&report)
+        .arg(root.as_ref().join("tree"))
+        .assert()
+        .success();
+    let output = assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["verify", "--json"])
+        .arg(&report)
+        .assert()
+        .success()
+        .stderr(predstr::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let outcome: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(
+        outcome["holds"][0]["files"],
+        serde_json::json!([alone.to_string_lossy()])
+    );
+    assert!(outcome["split"].as_array().is_some_and(Vec::is_empty));
+    assert!(outcome["unreadable"].as_array().is_some_and(Vec::is_empty));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn verify_reads_back_paths_which_arent_utf8() -> AnyResult {
+    use std::os::unix::ffi::OsStringExt;
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(8192);
+    let tree = root.as_ref().join("tree");
+    std::fs::create_dir(&tree)?;
+    let a = tree.join(std::ffi::OsString::from_vec(b"a\xe7".to_vec()));
+    let b = tree.join("b");
+    std::fs::write(&a, &bytes)?;
+    std::fs::write(&b, &bytes)?;
+    let report = root.as_ref().join("report.json");
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--format", "report", "--output"])
+        .arg(&report)
+        .arg(&tree)
+        .assert()
+        .success();
+    let output = assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["verify", "--json"])
+        .arg(&report)
+        .assert()
+        .success()
+        .stderr(predstr::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let outcome: serde_json::Value = serde_json::from_slice(&output)?;
+    assert_eq!(outcome["holds"].as_array().map(Vec::len), Some(1));
+    assert!(outcome["disappeared"].as_array().is_some_and(Vec::is_empty));
+    Ok(())
+}
+
+#[test]
+fn manifest_and_check() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(8192);
+    std::fs::create_dir(root.as_ref().join("tree"))?;
+    let a = root.write_file("tree/a", &bytes)?;
+    let b = root.write_file("tree/b", &bytes)?;
+    root.write_file("tree/small", &bytes[..100])?;
+    root.write_file("tree/small_copy", &bytes[..100])?;
+    let manifest = root.as_ref().join("manifest");
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--algorithm", "seahash", "--manifest"])
+        .arg(&manifest)
+        .arg(root.as_ref().join("tree"))
+        .assert()
+        .success()
+        .stderr(predstr::is_empty());
+    let hash = seahash_hex(&bytes);
+    assert_eq!(
+        std::fs::read_to_string(&manifest)?,
+        format!("{hash}  {}\n{hash}  {}\n", a.display(), b.display())
+    );
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["check", "--algorithm", "seahash"])
+        .arg(&manifest)
+        .assert()
+        .success()
+        .stdout(format!("{}: OK\n{}: OK\n", a.display(), b.display()));
+    root.write_file("tree/a", &bytes[..200])?;
+    std::fs::remove_file(&b)?;
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["check", "--algorithm", "seahash"])
+        .arg(&manifest)
+        .assert()
+        .failure()
+        .stdout(format!(
+            "{}: MODIFIED\n{}: MISSING\n",
+            a.display(),
+            b.display()
+        ))
+        .stderr(predstr::contains("2 of 2 files did not match"));
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["check", "--algorithm", "ahash"])
+        .arg(&manifest)
+        .assert()
+        .failure()
+        .stderr(predstr::contains("change from one run to the next"));
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--algorithm", "ahash", "--manifest"])
+        .arg(&manifest)
+        .arg(root.as_ref().join("tree"))
+        .assert()
+        .failure()
+        .stderr(predstr::contains("change from one run to the next"));
+    Ok(())
+}
+
+#[test]
+fn snapshot_and_diff() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(8192);
+    std::fs::create_dir(root.as_ref().join("tree"))?;
+    let kept = root.write_file("tree/kept", &bytes)?;
+    let moved = root.write_file("tree/moved", &bytes[..300])?;
+    let edited = root.write_file("tree/edited", &bytes[..400])?;
+    let deleted = root.write_file("tree/deleted", &bytes[..500])?;
+    let tree = root.as_ref().join("tree");
+    let snapshot = root.as_ref().join("before.sums");
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["snapshot", "--algorithm", "seahash", "--output"])
+        .arg(&snapshot)
+        .arg(&tree)
+        .assert()
+        .success();
+    let renamed = tree.join("renamed");
+    std::fs::rename(&moved, &renamed)?;
+    let copy = root.write_file("tree/kept copy", &bytes)?;
+    let added = root.write_file("tree/new", &bytes[..600])?;
+    root.write_file("tree/edited", &bytes[..401])?;
+    std::fs::remove_file(&deleted)?;
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["diff", "--algorithm", "seahash"])
+        .arg(&snapshot)
+        .arg(&tree)
+        .assert()
+        .success()
+        .stdout(format!(
+            "deleted {}
+modified {}
+copied {} -> {}
+added {}
+moved {} -> {}
+",
+            deleted.display(),
+            edited.display(),
+            kept.display(),
+            copy.display(),
+            added.display(),
+            moved.display(),
+            renamed.display(),
+        ));
+    let output = assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["diff", "--json", "--algorithm", "seahash"])
+        .arg(&snapshot)
+        .arg(&tree)
+        .output()?;
+    let changes: serde_json::Value = serde_json::from_slice(&output.stdout)?;
+    assert_eq!(changes[4]["change"], "moved");
+    assert_eq!(changes[4]["from"], moved.to_str().unwrap());
+    assert_eq!(changes[4]["to"], renamed.to_str().unwrap());
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["snapshot", "--algorithm", "ahash"])
+        .arg(&tree)
+        .assert()
+        .failure()
+        .stderr(predstr::contains("change from one run to the next"));
+    Ok(())
+}
+
+#[test]
+fn diff_against_an_every_file_report() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(8192 * 2);
+    std::fs::create_dir(root.as_ref().join("tree"))?;
+    let kept = root.write_file("tree/kept", &bytes[..8192])?;
+    let moved = root.write_file("tree/moved", &bytes[..300])?;
+    let edited = root.write_file("tree/edited", &bytes[8192..])?;
+    let deleted = root.write_file("tree/deleted", &bytes[..500])?;
+    let tree = root.as_ref().join("tree");
+    let report = root.as_ref().join("report.json");
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args([
+            "--algorithm",
+            "seahash",
+            "--format",
+            "report",
+            "--every-file",
+        ])
+        .arg("--output")
+        .arg(&report)
+        .arg(&tree)
+        .assert()
+        .success();
+    let renamed = tree.join("renamed");
+    std::fs::rename(&moved, &renamed)?;
+    let copy = root.write_file("tree/kept copy", &bytes[..8192])?;
+    root.write_file("tree/edited", &bytes[8191..])?;
+    std::fs::remove_file(&deleted)?;
+    // The algorithm is the report's, whatever the default.
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .arg("diff")
+        .arg(&report)
+        .arg(&tree)
+        .assert()
+        .success()
+        .stdout(format!(
+            "deleted {}
+modified {}
+copied {} -> {}
+moved {} -> {}
+",
+            deleted.display(),
+            edited.display(),
+            kept.display(),
+            copy.display(),
+            moved.display(),
+            renamed.display(),
+        ));
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--algorithm", "seahash", "--format", "report", "--output"])
+        .arg(&report)
+        .arg(&tree)
+        .assert()
+        .success();
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .arg("diff")
+        .arg(&report)
+        .arg(&tree)
+        .assert()
+        .failure()
+        .stderr(predstr::contains("make it with `--every-file`"));
+    Ok(())
+}
+
+#[test]
+fn merge_reports_from_hosts() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let shared: Vec<_> = random_collection(8192);
+    let local: Vec<_> = random_collection(5000);
+    std::fs::create_dir(root.as_ref().join("alpha"))?;
+    std::fs::create_dir(root.as_ref().join("beta"))?;
+    let a1 = root.write_file("alpha/1", &shared)?;
+    let a2 = root.write_file("alpha/2", &shared)?;
+    let b1 = root.write_file("beta/1", &shared)?;
+    let b2 = root.write_file("beta/2", &shared)?;
+    root.write_file("beta/3", &local)?;
+    root.write_file("beta/4", &local)?;
+    // A single copy on each host is a duplicate all the same.
+    let once: Vec<_> = random_collection(6000);
+    let a5 = root.write_file("alpha/5", &once)?;
+    let b5 = root.write_file("beta/5", &once)?;
+    root.write_file("alpha/6", random_collection::<u8, Vec<_>>(7000))?;
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--format", "report", "--algorithm", "seahash", "--output"])
+        .arg(root.as_ref().join("duplicates.json"))
+        .arg(root.as_ref().join("alpha"))
+        .assert()
+        .success();
+    for host in ["alpha", "beta"] {
+        assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+            .args([
+                "--format",
+                "report",
+                "--every-file",
+                "--algorithm",
+                "seahash",
+            ])
+            .arg("--output")
+            .arg(root.as_ref().join(format!("{host}.json")))
+            .arg(root.as_ref().join(host))
+            .assert()
+            .success();
+    }
+    let output = assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["merge", "--json"])
+        .arg(root.as_ref().join("alpha.json"))
+        .arg(format!("b={}", root.as_ref().join("beta.json").display()))
+        .assert()
+        .success()
+        .stderr(predstr::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let merged: serde_json::Value = serde_json::from_slice(&output)?;
+    let file = |host: &str, path: &std::path::Path| serde_json::json!({"host": host, "path": path.to_string_lossy()});
+    assert_eq!(merged["algorithm"], "seahash");
+    let group = |content: &[u8]| {
+        let group = merged["groups"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|group| group["hash"] == seahash_hex(content));
+        let mut files = group
+            .and_then(|group| group["files"].as_array().cloned())
+            .unwrap_or_default();
+        files.sort_by_key(|file| file["path"].as_str().map(str::to_owned));
+        files
+    };
+    assert_eq!(merged["groups"].as_array().map(Vec::len), Some(2));
+    assert_eq!(
+        group(&shared),
+        [
+            file("alpha", &a1),
+            file("alpha", &a2),
+            file("b", &b1),
+            file("b", &b2)
+        ]
+    );
+    assert_eq!(group(&once), [file("alpha", &a5), file("b", &b5)]);
+    assert_eq!(
+        merged["hosts"],
+        serde_json::json!([
+            {"host": "alpha", "files": 3, "freeable": 22384},
+            {"host": "b", "files": 3, "freeable": 22384},
+        ])
+    );
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .arg("merge")
+        .arg(root.as_ref().join("duplicates.json"))
+        .arg(root.as_ref().join("beta.json"))
+        .assert()
+        .failure()
+        .stderr(predstr::contains("make it with `--every-file`"));
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .arg("merge")
+        .arg(root.as_ref().join("alpha.json"))
+        .arg(root.as_ref().join("alpha.json"))
+        .assert()
+        .failure()
+        .stderr(predstr::contains("two reports are labelled \"alpha\""));
+    Ok(())
+}
+
+#[test]
+fn rich_output_is_only_for_files() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--format", "csv-rich", "--directories"])
+        .arg(root.as_ref())
+        .assert()
+        .failure()
+        .stderr(predstr::contains("only available for duplicate files"));
+    Ok(())
+}
+
+#[test]
+fn known_hashes_need_a_stable_algorithm() -> AnyResult {
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--algorithm", "ahash", "--known", "list"])
+        .assert()
+        .failure()
+        .stderr(predstr::contains("can't be compared with known hashes"));
+    Ok(())
+}
+
+#[test]
+fn cryptographic_hashes_match_the_usual_tools() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    std::fs::create_dir(root.as_ref().join("tree"))?;
+    let abc = root.write_file("tree/abc", b"abc")?;
+    let cases = [
+        (
+            "sha256",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+        ),
+        ("sha1", "a9993e364706816aba3e25717850c26c9cd0d89d"),
+        ("md5", "900150983cd24fb0d6963f7d28e17f72"),
+        ("crc32c", "364b3fb7"),
+    ];
+    for (algorithm, digest) in cases {
+        let list = root.write_file(algorithm, format!("{digest}  abc\n"))?;
+        assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+            .args(["--algorithm", algorithm])
+            .arg("--known")
+            .arg(&list)
+            .arg(root.as_ref().join("tree"))
+            .assert()
+            .success()
+            .stdout(format!("{}\n", abc.display()))
+            .stderr(predstr::is_empty());
+    }
+    Ok(())
+}
+
+#[test]
+fn files_from_scans_only_the_listed_files() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(MAX_LEN);
+    std::fs::create_dir_all(root.as_ref().join("sub"))?;
+    let file1 = root.write_file("file1", &bytes)?;
+    let file2 = root.write_file("sub/file2", &bytes)?;
+    root.write_file("unlisted", &bytes)?;
+    // The same file under another name is only scanned once.
+    let again = root.as_ref().join("sub").join("..").join("file1");
+    let list = format!(
+        "{}\n{}\r\n\n{}\n{}\n",
+        file1.display(),
+        file2.display(),
+        root.as_ref().join("sub").display(),
+        again.display()
+    );
+    let output = assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--files-from", "-"])
+        .write_stdin(list)
+        .assert()
+        .success()
+        .stderr(predstr::is_empty())
+        .get_output()
+        .stdout
+        .clone();
+    let output = String::from_utf8(output)?;
+    let listed: std::collections::BTreeSet<_> = output.lines().collect();
+    let expected = [file1.display().to_string(), file2.display().to_string()];
+    assert_eq!(output.lines().count(), 2);
+    assert_eq!(listed, expected.iter().map(String::as_str).collect());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn files_from_leaves_out_links() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(4096);
+    root.write_file("file1", &bytes)?;
+    let file2 = root.write_file("file2", &bytes)?;
+    let link = root.as_ref().join("link");
+    std::os::unix::fs::symlink("file1", &link)?;
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--files-from", "-"])
+        .write_stdin(format!("{}\n{}\n", link.display(), file2.display()))
+        .assert()
+        .success()
+        .stdout("\n")
+        .stderr(predstr::is_empty());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn files0_from_keeps_non_utf8_paths() -> AnyResult {
+    use std::os::unix::ffi::OsStrExt;
+    let root = TestDir::new(test_dir!())?;
+    let bytes: Vec<_> = random_collection(4096);
+    let name = std::ffi::OsStr::from_bytes(b"non\xffutf8\nname");
+    let file1 = root.write_file(name, &bytes)?;
+    let file2 = root.write_file("file2", &bytes)?;
+    let mut list = Vec::new();
+    for file in [&file1, &file2] {
+        list.extend_from_slice(file.as_os_str().as_bytes());
+        list.push(b'\0');
+    }
+    let list_file = root.write_file("list", &list)?;
+    // A name which isn't UTF-8 is written as its bytes, to be read back as is.
+    let raw: Vec<_> = file1
+        .as_os_str()
+        .as_bytes()
+        .iter()
+        .map(u8::to_string)
+        .collect();
+    assert_cmd::Command::cargo_bin(assert_cmd::pkg_name!())?
+        .args(["--format", "json"])
+        .arg("--files0-from")
+        .arg(&list_file)
+        .assert()
+        .success()
+        .stdout(predstr::contains(format!("[{}]", raw.join(","))).and(predstr::contains("file2")))
+        .stderr(predstr::is_empty());
+    Ok(())
+}
@@ -108,3 +108,396 @@ fn files_differing_by_middle() -> AnyResult {
     assert_eq!(counter.as_inner().len(), 2);
     Ok(())
 }
+
+#[test]
+fn spawned_scan_reports_progress() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let buffer: Vec<_> = random_collection(MAX_LEN);
+    root.write_file("file1", &buffer)?;
+    root.write_file("file2", &buffer)?;
+    root.write_file("file3", b"aaa")?;
+    let scan = yadf::Yadf::builder()
+        .paths(vec![root.as_ref().to_path_buf()])
+        .build()
+        .spawn::<seahash::SeaHasher>();
+    while !scan.is_finished() {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    let progress = scan.progress();
+    assert_eq!(progress.phase, yadf::Phase::Finished);
+    assert_eq!(progress.found, 3);
+    let counter = scan.join().expect("the scan wasn't cancelled");
+    assert_eq!(counter.duplicates().iter().count(), 1);
+    Ok(())
+}
+
+#[test]
+fn cancelled_scan_gives_nothing_back() -> AnyResult {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+    let root = TestDir::new(test_dir!())?;
+    root.write_file("file1", b"aaa")?;
+    root.write_file("file2", b"aaa")?;
+    let config = yadf::Yadf::builder()
+        .paths(vec![root.as_ref().to_path_buf()])
+        .build();
+    assert_send_sync(&config);
+    let scan = config.spawn::<seahash::SeaHasher>();
+    scan.cancel();
+    assert!(scan.join().is_none());
+    Ok(())
+}
+
+#[test]
+fn scan_cancelled_once_done_gives_everything_back() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    root.write_file("file1", b"aaa")?;
+    root.write_file("file2", b"aaa")?;
+    let scan = yadf::Yadf::builder()
+        .paths(vec![root.as_ref().to_path_buf()])
+        .build()
+        .spawn::<seahash::SeaHasher>();
+    while !scan.is_finished() {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    scan.cancel();
+    let counter = scan
+        .join()
+        .expect("the scan was done before it was cancelled");
+    assert_eq!(counter.duplicates().iter().count(), 1);
+    Ok(())
+}
+
+#[test]
+fn scan_of_a_memory_filesystem() -> AnyResult {
+    let buffer: Vec<_> = random_collection(MAX_LEN);
+    let mut other = buffer.clone();
+    *other.last_mut().unwrap() ^= 1;
+    let mut files = yadf::vfs::MemoryFileSystem::default();
+    files.insert("/tree/file1", &buffer);
+    files.insert("/tree/sub/file2", &buffer);
+    files.insert("/tree/file3", &other);
+    files.insert("/elsewhere/file4", &buffer);
+    let counter = yadf::Yadf::builder()
+        .paths(["/tree"])
+        .filesystem(files)
+        .build()
+        .scan::<seahash::SeaHasher>();
+    let duplicates = counter.duplicates();
+    let duplicates: Vec<_> = duplicates.iter().collect();
+    assert_eq!(duplicates.len(), 1);
+    let mut paths: Vec<&std::path::Path> = duplicates[0].iter().map(AsRef::as_ref).collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        ["/tree/file1", "/tree/sub/file2"].map(std::path::Path::new)
+    );
+    Ok(())
+}
+
+#[test]
+fn predicates_filter_files_and_prune_directories() -> AnyResult {
+    let mut files = yadf::vfs::MemoryFileSystem::default();
+    for path in [
+        "/tree/a",
+        "/tree/b.keep",
+        "/tree/c",
+        "/tree/vendor/d",
+        "/tree/vendor/e",
+    ] {
+        files.insert(path, "same");
+    }
+    let counter = yadf::Yadf::builder()
+        .paths(["/tree"])
+        .filesystem(files)
+        .file_predicate(|path, _| path.extension().is_none_or(|ext| ext != "keep"))
+        .directory_predicate(|path, _| !path.ends_with("vendor"))
+        .build()
+        .scan::<seahash::SeaHasher>();
+    let duplicates = counter.duplicates();
+    let duplicates: Vec<_> = duplicates.iter().collect();
+    assert_eq!(duplicates.len(), 1);
+    let mut paths: Vec<&std::path::Path> = duplicates[0].iter().map(AsRef::as_ref).collect();
+    paths.sort();
+    assert_eq!(paths, ["/tree/a", "/tree/c"].map(std::path::Path::new));
+    Ok(())
+}
+
+#[test]
+fn stages_one_by_one_match_a_scan() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let buffer: Vec<_> = random_collection(MAX_LEN);
+    let mut other = buffer.clone();
+    *other.last_mut().unwrap() ^= 1;
+    root.write_file("large1", &buffer)?;
+    root.write_file("large2", &buffer)?;
+    root.write_file("large3", &other)?;
+    root.write_file("small1", b"aaa")?;
+    root.write_file("small2", b"aaa")?;
+    root.write_file("alone", &buffer[..100])?;
+    let config = yadf::Yadf::builder()
+        .paths(vec![root.as_ref().to_path_buf()])
+        .build();
+    let by_size = config.walk();
+    assert_eq!(by_size.len(), 3);
+    let by_prefix = config.hash_partial::<seahash::SeaHasher>(by_size);
+    let large = by_prefix
+        .as_inner()
+        .values()
+        .find(|bucket| bucket.len() == 3)
+        .expect("the large files share their first 4 KiB");
+    assert!(large
+        .iter()
+        .all(|candidate| candidate.size() == MAX_LEN as u64));
+    // The bags between stages can be saved and picked up again.
+    let saved = serde_json::to_string(&by_prefix)?;
+    let by_prefix: yadf::TreeBag<u64, yadf::Candidate> = serde_json::from_str(&saved)?;
+    let large = by_prefix
+        .as_inner()
+        .values()
+        .find(|bucket| bucket.len() == 3)
+        .expect("the large files are read back");
+    assert!(large
+        .iter()
+        .all(|candidate| candidate.size() == MAX_LEN as u64));
+    let by_suffix = config.hash_suffix::<seahash::SeaHasher>(by_prefix);
+    assert_eq!(by_suffix.len(), 4);
+    let staged = config.hash_full::<seahash::SeaHasher>(by_suffix);
+    let scanned = config.scan::<seahash::SeaHasher>();
+    assert_eq!(
+        staged.as_inner().keys().collect::<Vec<_>>(),
+        scanned.as_inner().keys().collect::<Vec<_>>()
+    );
+    assert_eq!(staged.duplicates().iter().count(), 2);
+    Ok(())
+}
+
+#[test]
+fn files_of_different_sizes_sharing_a_tail_stay_apart() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let head: Vec<_> = random_collection(MAX_LEN);
+    let tail: Vec<_> = random_collection(8192);
+    // Two files of each size, sharing their head: one of each ends like the
+    // other size's, the other ends its own way.
+    for (name, len) in [("short", MAX_LEN / 2), ("long", MAX_LEN)] {
+        let mut shared = head[..len].to_vec();
+        shared.extend_from_slice(&tail);
+        root.write_file(format!("{name}_shared_tail"), &shared)?;
+        let mut own = head[..len].to_vec();
+        own.extend_from_slice(&random_collection::<_, Vec<_>>(8192));
+        root.write_file(format!("{name}_own_tail"), &own)?;
+    }
+    let config = yadf::Yadf::builder()
+        .paths(vec![root.as_ref().to_path_buf()])
+        .build();
+    let by_prefix = config.hash_partial::<seahash::SeaHasher>(config.walk());
+    let by_suffix = config.hash_suffix::<seahash::SeaHasher>(by_prefix);
+    for bucket in by_suffix.as_inner().values() {
+        assert!(bucket.iter().all(|file| file.size() == bucket[0].size()));
+    }
+    assert_eq!(by_suffix.len(), 4);
+    let counter = find_dupes(&root);
+    assert_eq!(counter.duplicates().iter().count(), 0);
+    Ok(())
+}
+
+#[test]
+fn configured_walker_honours_ignore_files() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    root.write_file(".ignore", b"skipped*\n")?;
+    root.write_file("kept1", b"aaa")?;
+    root.write_file("kept2", b"aaa")?;
+    root.write_file("skipped1", b"bbb")?;
+    root.write_file("skipped2", b"bbb")?;
+    let files = yadf::vfs::Os::with_walker(|walker| {
+        walker.ignore(true);
+    });
+    let counter = yadf::Yadf::builder()
+        .paths(vec![root.as_ref().to_path_buf()])
+        .filesystem(files)
+        .build()
+        .scan::<seahash::SeaHasher>();
+    let duplicates = counter.duplicates();
+    let duplicates: Vec<_> = duplicates.iter().collect();
+    assert_eq!(duplicates.len(), 1);
+    let mut names: Vec<_> = duplicates[0]
+        .iter()
+        .map(|path| path.as_ref().file_name().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["kept1", "kept2"]);
+    Ok(())
+}
+
+#[test]
+fn io_uring_reads_match_the_usual_ones() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let buffer: Vec<_> = random_collection(MAX_LEN * 2);
+    let mut other = buffer.clone();
+    *other.last_mut().unwrap() ^= 1;
+    root.write_file("large1", &buffer)?;
+    root.write_file("large2", &buffer)?;
+    root.write_file("large3", &other)?;
+    root.write_file("small1", b"aaa")?;
+    root.write_file("small2", b"aaa")?;
+    root.write_file("empty1", b"")?;
+    root.write_file("empty2", b"")?;
+    let scan = |io_uring| {
+        yadf::Yadf::builder()
+            .paths(vec![root.as_ref().to_path_buf()])
+            .filesystem(yadf::vfs::Os::default().io_uring(io_uring))
+            .build()
+            .scan::<seahash::SeaHasher>()
+    };
+    let batched = scan(true);
+    let usual = scan(false);
+    assert_eq!(
+        batched.as_inner().keys().collect::<Vec<_>>(),
+        usual.as_inner().keys().collect::<Vec<_>>()
+    );
+    assert_eq!(batched.duplicates().iter().count(), 3);
+    Ok(())
+}
+
+#[test]
+fn batched_stats_match_the_usual_ones() -> AnyResult {
+    let root = TestDir::new(test_dir!())?;
+    let buffer: Vec<_> = random_collection(MAX_LEN);
+    for index in 0..600 {
+        root.write_file(format!("file{index}"), &buffer[..index % 7])?;
+    }
+    std::fs::create_dir(root.as_ref().join("sub"))?;
+    root.write_file("sub/large1", &buffer)?;
+    root.write_file("sub/large2", &buffer)?;
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("large1", root.as_ref().join("sub/link"))?;
+    let scan = |io_uring, stat_in_batches, follow_links| {
+        let files = yadf::vfs::Os::with_walker(move |walker| {
+            walker.follow_links(follow_links);
+        })
+        .io_uring(io_uring)
+        .stat_in_batches(stat_in_batches);
+        let config = yadf::Yadf::builder()
+            .paths(vec![root.as_ref().to_path_buf()])
+            .filesystem(files)
+            .file_info(true);
+        // The link followed is the very file it points to.
+        #[cfg(unix)]
+        let config = config.hard_links(true);
+        let counter = config.build().scan::<seahash::SeaHasher>();
+        let mut files: Vec<_> = counter
+            .into_inner()
+            .into_values()
+            .flatten()
+            .map(|path| (path.as_ref().to_path_buf(), path.info().cloned()))
+            .collect();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        files
+    };
+    let usual = scan(false, false, false);
+    assert_eq!(usual.len(), 602);
+    assert_eq!(scan(false, true, false), usual);
+    assert_eq!(scan(true, true, false), usual);
+    // A link to a file is one more file once links are followed.
+    let followed = scan(false, false, true);
+    assert_eq!(followed.len(), if cfg!(unix) { 603 } else { 602 });
+    assert_eq!(scan(false, true, true), followed);
+    assert_eq!(scan(true, true, true), followed);
+    Ok(())
+}
+
+/// A [`MemoryFileSystem`](yadf::vfs::MemoryFileSystem) unable to open one of
+/// its files.
+#[derive(Debug)]
+struct Unreadable {
+    files: yadf::vfs::MemoryFileSystem,
+    broken: &'static str,
+}
+
+impl yadf::vfs::FileSystem for Unreadable {
+    fn walk(
+        &self,
+        roots: &[&std::path::Path],
+        max_depth: Option<usize>,
+        visit: &(dyn Fn(std::io::Result<yadf::vfs::Entry>) -> yadf::vfs::Visit + Sync),
+    ) {
+        self.files.walk(roots, max_depth, visit)
+    }
+
+    fn metadata(&self, path: &std::path::Path) -> std::io::Result<yadf::vfs::Metadata> {
+        self.files.metadata(path)
+    }
+
+    fn open(
+        &self,
+        path: &std::path::Path,
+        access: yadf::vfs::Access,
+    ) -> std::io::Result<Box<dyn yadf::vfs::FileReader + '_>> {
+        if path == std::path::Path::new(self.broken) {
+            return Err(std::io::ErrorKind::PermissionDenied.into());
+        }
+        self.files.open(path, access)
+    }
+}
+
+#[test]
+fn unreadable_sources_are_unverified_rather_than_missing() -> AnyResult {
+    let buffer: Vec<_> = random_collection(MAX_LEN);
+    let mut files = yadf::vfs::MemoryFileSystem::default();
+    files.insert("/source/kept", &buffer);
+    files.insert("/source/broken", &buffer[..100]);
+    files.insert("/source/new", &buffer[..200]);
+    files.insert("/backup/kept", &buffer);
+    files.insert("/backup/other", &buffer[200..300]);
+    let fs = Unreadable {
+        files,
+        broken: "/source/broken",
+    };
+    let result = yadf::Yadf::builder()
+        .paths(["/source"])
+        .filesystem(fs)
+        .build()
+        .scan_missing_from::<seahash::SeaHasher, _>(&["/backup"]);
+    let paths = |paths: &[yadf::Path]| -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| path.as_ref().display().to_string())
+            .collect()
+    };
+    assert_eq!(paths(&result.missing), ["/source/new"]);
+    assert_eq!(paths(&result.unverified), ["/source/broken"]);
+    Ok(())
+}
+
+/// A hasher of one's own, its hash no more than comparable.
+#[derive(Default)]
+struct Sum(u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct SumHash(u64);
+
+impl yadf::Hasher for Sum {
+    type Hash = SumHash;
+    fn write(&mut self, buf: &[u8]) {
+        for &byte in buf {
+            self.0 = self.0.wrapping_mul(31).wrapping_add(u64::from(byte));
+        }
+    }
+    fn finish(self) -> Self::Hash {
+        SumHash(self.0)
+    }
+}
+
+#[test]
+fn hashers_of_ones_own_scan_without_byte_encoding() -> AnyResult {
+    let mut files = yadf::vfs::MemoryFileSystem::default();
+    files.insert("/tree/a", "same");
+    files.insert("/tree/b", "same");
+    files.insert("/tree/c", "else");
+    let counter = yadf::Yadf::builder()
+        .paths(["/tree"])
+        .filesystem(files)
+        .build()
+        .scan::<Sum>();
+    assert_eq!(counter.duplicates().iter().count(), 1);
+    Ok(())
+}